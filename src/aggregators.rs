@@ -0,0 +1,78 @@
+// src/aggregators.rs
+use crate::utils;
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// CoinMarketCap rejects supply values with more than 8 decimal places
+pub const CMC_DECIMAL_PLACES: u8 = 8;
+
+/// Supply figures in wei, read once per request and rendered in each aggregator's format.
+#[derive(Debug, Clone, Copy)]
+pub struct SupplyFigures {
+    pub total: U256,
+    pub circulating: U256,
+    pub max: Option<U256>,
+}
+
+/// A single figure requested on a per-figure aggregator route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SupplyFigure {
+    Total,
+    Circulating,
+    Max,
+}
+
+impl SupplyFigures {
+    pub fn get(&self, figure: SupplyFigure) -> Option<U256> {
+        match figure {
+            SupplyFigure::Total => Some(self.total),
+            SupplyFigure::Circulating => Some(self.circulating),
+            SupplyFigure::Max => self.max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SupplyValues {
+    pub total_supply: String,
+    pub circulating_supply: String,
+    /// `null` when no max supply is configured.
+    pub max_supply: Option<String>,
+}
+
+/// CoinGecko reads supply from a JSON `result` field.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CoinGeckoResult<T> {
+    pub result: T,
+}
+
+/// All figures at full token precision, wrapped in `result`.
+pub fn coingecko_supply(figures: &SupplyFigures, decimals: u8) -> CoinGeckoResult<SupplyValues> {
+    CoinGeckoResult { result: values(figures, |value| utils::u256_to_human(value, decimals)) }
+}
+
+pub fn coingecko_figure(value: U256, decimals: u8) -> CoinGeckoResult<String> {
+    CoinGeckoResult { result: utils::u256_to_human(value, decimals) }
+}
+
+/// All figures truncated to [`CMC_DECIMAL_PLACES`].
+pub fn cmc_supply(figures: &SupplyFigures, decimals: u8) -> SupplyValues {
+    values(figures, |value| cmc_figure(value, decimals))
+}
+
+/// A plain-text number truncated to [`CMC_DECIMAL_PLACES`], as CoinMarketCap's supply
+/// endpoints expect.
+pub fn cmc_figure(value: U256, decimals: u8) -> String {
+    let places = decimals.min(CMC_DECIMAL_PLACES);
+    utils::u256_to_human(value / U256::exp10((decimals - places) as usize), places)
+}
+
+fn values(figures: &SupplyFigures, format: impl Fn(U256) -> String) -> SupplyValues {
+    SupplyValues {
+        total_supply: format(figures.total),
+        circulating_supply: format(figures.circulating),
+        max_supply: figures.max.map(&format),
+    }
+}
@@ -0,0 +1,221 @@
+// src/alerts.rs
+//! Turns successive supply refreshes into alerts: circulating supply moving past a threshold,
+//! scheduled unlocks, burn balance changes, repeated read failures, and vesting violations.
+use crate::config::{AllocationLabel, PoolSchedule};
+use crate::monitor::Violation;
+use crate::supply::{ReleaseGranularity, SupplyBreakdown, calculate_schedule_vesting};
+use crate::utils;
+use crate::webhook::Notification;
+use ethers::types::{Address, U256};
+use serde::Serialize;
+
+pub const DEFAULT_SUPPLY_CHANGE_BPS: u64 = 100;
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    /// Change in circulating supply between two refreshes, in basis points of the earlier
+    /// figure, beyond which an alert is raised.
+    pub supply_change_bps: u64,
+    /// Consecutive refreshes with failed reads before an alert is raised.
+    pub failure_threshold: u32,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self { supply_change_bps: DEFAULT_SUPPLY_CHANGE_BPS, failure_threshold: DEFAULT_FAILURE_THRESHOLD }
+    }
+}
+
+/// Parses a percentage such as `1` or `0.25` into basis points.
+pub fn parse_basis_points(value: &str) -> Result<u64, anyhow::Error> {
+    let percent = value.trim().parse::<f64>().map_err(|e| anyhow::anyhow!("Invalid percentage '{}': {}", value, e))?;
+    if !percent.is_finite() || percent < 0.0 {
+        return Err(anyhow::anyhow!("Invalid percentage '{}': must be zero or more", value));
+    }
+    Ok((percent * 100.0).round() as u64)
+}
+
+/// Body POSTed to generic JSON webhooks, tagged by `event`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Alert {
+    CirculatingSupplyChange {
+        block: u64,
+        timestamp: u64,
+        previous: String,
+        current: String,
+        /// Change as a percentage of `previous`, negative when supply fell.
+        change_percent: String,
+    },
+    /// A schedule released tokens at its start, cliff, a step or a tranche. Continuous linear
+    /// schedules only report the TGE share at their start, not the release that follows, even
+    /// right after the cliff.
+    Unlock {
+        block: u64,
+        timestamp: u64,
+        #[serde(flatten)]
+        label: AllocationLabel,
+        addresses: Vec<Address>,
+        /// Released since the previous refresh.
+        unlocked: String,
+        /// Still locked by the schedule.
+        locked: String,
+    },
+    BurnBalanceChange { block: u64, timestamp: u64, previous: String, current: String },
+    ReadFailures {
+        consecutive_failures: u32,
+        /// The most recent failure.
+        error: String,
+    },
+    VestingViolation { violation: Violation },
+}
+
+impl Notification for Alert {
+    fn summary(&self) -> String {
+        match self {
+            Self::CirculatingSupplyChange { block, previous, current, change_percent, .. } => {
+                let (direction, percent) = match change_percent.strip_prefix('-') {
+                    Some(percent) => ("fell", percent),
+                    None => ("rose", change_percent.as_str()),
+                };
+                format!("Circulating supply {} {}% from {} to {} MAT at block {}", direction, percent, previous, current, block)
+            }
+            Self::Unlock { block, label, unlocked, locked, .. } => {
+                format!("{} unlocked {} MAT at block {}, {} MAT still locked", describe(label), unlocked, block, locked)
+            }
+            Self::BurnBalanceChange { block, previous, current, .. } => format!("Burn balance changed from {} to {} MAT at block {}", previous, current, block),
+            Self::ReadFailures { consecutive_failures, error } => format!("Supply reads failed {} times in a row: {}", consecutive_failures, error),
+            Self::VestingViolation { violation } => format!(
+                "{} holds {} MAT, {} MAT below its {} MAT locked amount ({} outflow(s) at block {})",
+                describe(&violation.label),
+                violation.balance,
+                violation.shortfall,
+                violation.locked,
+                violation.outflows.len(),
+                violation.block,
+            ),
+        }
+    }
+}
+
+fn describe(label: &AllocationLabel) -> String {
+    match &label.name {
+        Some(name) => format!("{} ({})", name, label.category()),
+        None => format!("Unnamed {} allocation", label.category()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    block: u64,
+    timestamp: u64,
+    circulating: U256,
+    burn_balance: U256,
+}
+
+/// Compares every refresh with the previous successful one.
+#[derive(Debug, Clone)]
+pub struct SupplyWatcher {
+    config: AlertConfig,
+    schedules: Vec<PoolSchedule>,
+    tge_timestamp: U256,
+    decimals: u8,
+    previous: Option<Snapshot>,
+    consecutive_failures: u32,
+}
+
+impl SupplyWatcher {
+    pub fn new(config: AlertConfig, schedules: Vec<PoolSchedule>, tge_timestamp: U256, decimals: u8) -> Self {
+        Self { config, schedules, tge_timestamp, decimals, previous: None, consecutive_failures: 0 }
+    }
+
+    /// Alerts raised by a successful refresh. Failed supply and vesting reads inside it count
+    /// towards the failure streak, failed staking reads in `staking_failures` do not. The first
+    /// refresh only sets the baseline.
+    pub fn observe(&mut self, breakdown: &SupplyBreakdown) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        match breakdown.failures.first() {
+            Some(failure) => alerts.extend(self.record_failure(format!("{} on {:?} failed: {}", failure.function, failure.target, failure.reason))),
+            None => self.consecutive_failures = 0,
+        }
+
+        let current = Snapshot {
+            block: breakdown.block,
+            timestamp: breakdown.timestamp,
+            circulating: breakdown.circulating_supply_wei,
+            burn_balance: breakdown.burn_balance_wei,
+        };
+        let Some(previous) = self.previous.replace(current) else {
+            return alerts;
+        };
+        // A refresh served from an older block than the last one says nothing new
+        if current.block <= previous.block {
+            self.previous = Some(previous);
+            return alerts;
+        }
+
+        let change = previous.circulating.abs_diff(current.circulating);
+        if change.saturating_mul(U256::from(10_000u64)) > previous.circulating.saturating_mul(U256::from(self.config.supply_change_bps)) {
+            alerts.push(Alert::CirculatingSupplyChange {
+                block: current.block,
+                timestamp: current.timestamp,
+                previous: utils::u256_to_human(previous.circulating, self.decimals),
+                current: utils::u256_to_human(current.circulating, self.decimals),
+                change_percent: format!("{}{}", if current.circulating < previous.circulating { "-" } else { "" }, utils::percent(change, previous.circulating)),
+            });
+        }
+
+        alerts.extend(self.unlocks(previous, current));
+
+        if current.burn_balance != previous.burn_balance {
+            alerts.push(Alert::BurnBalanceChange {
+                block: current.block,
+                timestamp: current.timestamp,
+                previous: utils::u256_to_human(previous.burn_balance, self.decimals),
+                current: utils::u256_to_human(current.burn_balance, self.decimals),
+            });
+        }
+        alerts
+    }
+
+    /// Records a refresh that failed outright. Raises one alert per streak, when it reaches the
+    /// threshold.
+    pub fn failed(&mut self, error: &anyhow::Error) -> Vec<Alert> {
+        self.record_failure(error.to_string()).into_iter().collect()
+    }
+
+    fn record_failure(&mut self, error: String) -> Option<Alert> {
+        self.consecutive_failures += 1;
+        (self.consecutive_failures == self.config.failure_threshold.max(1)).then_some(Alert::ReadFailures { consecutive_failures: self.consecutive_failures, error })
+    }
+
+    /// Schedules whose locked amount dropped between the two refreshes, from the configured
+    /// schedules rather than wallet balances so transfers are not mistaken for unlocks.
+    fn unlocks(&self, previous: Snapshot, current: Snapshot) -> Vec<Alert> {
+        let precision = U256::from(1_000_000u64);
+        let mut alerts = Vec::new();
+        for schedule in &self.schedules {
+            let before = calculate_schedule_vesting(schedule, precision, U256::from(previous.timestamp), self.tge_timestamp);
+            let after = calculate_schedule_vesting(schedule, precision, U256::from(current.timestamp), self.tge_timestamp);
+            if after.locked_amount >= before.locked_amount {
+                continue;
+            }
+            // Continuous linear release only has a lump at the start, when the TGE share unlocks
+            let started = U256::from(previous.timestamp) >= schedule.start(self.tge_timestamp);
+            let releasing_continuously = schedule.vesting_type == "linear" && schedule.release_granularity == ReleaseGranularity::Continuous && (started || schedule.tge_percentage.is_zero());
+            if releasing_continuously {
+                continue;
+            }
+            alerts.push(Alert::Unlock {
+                block: current.block,
+                timestamp: current.timestamp,
+                label: schedule.label.clone(),
+                addresses: schedule.addresses.iter().map(|(addr, _)| *addr).collect(),
+                unlocked: utils::u256_to_human(before.locked_amount - after.locked_amount, self.decimals),
+                locked: utils::u256_to_human(after.locked_amount, self.decimals),
+            });
+        }
+        alerts
+    }
+}
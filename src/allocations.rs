@@ -0,0 +1,100 @@
+// src/allocations.rs
+use crate::config::AllocationLabel;
+use crate::supply::SupplyBreakdown;
+use crate::utils;
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// What allocation shares are a percentage of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SupplyBasis {
+    /// The configured max supply.
+    Max,
+    /// Total supply across chains, used when no max supply is configured.
+    Total,
+}
+
+/// The vesting schedules as a tokenomics table, with per-category totals.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AllocationsReport {
+    pub block: u64,
+    pub timestamp: u64,
+    pub supply_basis: SupplyBasis,
+    pub basis_supply: String,
+    pub allocations: Vec<Allocation>,
+    pub categories: Vec<CategoryAllocation>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Allocation {
+    #[serde(flatten)]
+    pub label: AllocationLabel,
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Address>,
+    pub allocated: String,
+    /// Allocation as a percentage of the basis supply.
+    pub share_percent: String,
+    pub vesting_type: String,
+    pub start_timestamp: u64,
+    pub tge_percentage: u64,
+    pub cliff_days: u64,
+    pub vesting_days: u64,
+    pub unlocked: String,
+    pub locked: String,
+    pub unlocked_percent: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CategoryAllocation {
+    pub category: String,
+    pub allocated: String,
+    pub share_percent: String,
+    pub unlocked: String,
+    pub locked: String,
+    pub excluded_balance: String,
+}
+
+/// Builds the table from a supply breakdown, with shares of `basis_supply` (in wei).
+pub fn allocations_report(breakdown: &SupplyBreakdown, supply_basis: SupplyBasis, basis_supply: U256, decimals: u8) -> AllocationsReport {
+    let allocations = breakdown
+        .schedules
+        .iter()
+        .map(|schedule| Allocation {
+            label: schedule.label.clone(),
+            addresses: schedule.addresses.clone(),
+            allocated: schedule.initial.clone(),
+            share_percent: utils::percent(schedule.initial_wei, basis_supply),
+            vesting_type: schedule.vesting_type.clone(),
+            start_timestamp: schedule.start_timestamp,
+            tge_percentage: schedule.tge_percentage,
+            cliff_days: schedule.cliff_days,
+            vesting_days: schedule.vesting_days,
+            unlocked: utils::u256_to_human(schedule.initial_wei.saturating_sub(schedule.locked_wei), decimals),
+            locked: schedule.locked.clone(),
+            unlocked_percent: schedule.unlocked_percent,
+        })
+        .collect();
+    let categories = breakdown
+        .categories
+        .iter()
+        .map(|category| CategoryAllocation {
+            category: category.category.clone(),
+            allocated: category.allocated.clone(),
+            share_percent: utils::percent(category.allocated_wei, basis_supply),
+            unlocked: category.unlocked.clone(),
+            locked: category.locked.clone(),
+            excluded_balance: category.excluded_balance.clone(),
+        })
+        .collect();
+
+    AllocationsReport {
+        block: breakdown.block,
+        timestamp: breakdown.timestamp,
+        supply_basis,
+        basis_supply: utils::u256_to_human(basis_supply, decimals),
+        allocations,
+        categories,
+    }
+}
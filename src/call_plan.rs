@@ -0,0 +1,292 @@
+// src/call_plan.rs
+use crate::multicall::{CallFailure, FallibleCall};
+use crate::supply::PoolVestingMode;
+use crate::{ERC20, StakingPool};
+use ethers::abi::{AbiError, Token};
+use ethers::contract::ContractCall;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::fmt;
+
+/// A single on-chain value read for the circulating supply calculation. The same description
+/// drives both the call that is sent and how its result is decoded, so the two cannot drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedRead {
+    TotalSupply,
+    BurnBalance,
+    ExcludedBalance(Address),
+    ScheduleWalletBalance(Address),
+    ReservedPoolBalance(Address),
+    PoolInitialStake(Address),
+    PoolLockPeriod(Address),
+    PoolVestingDuration(Address),
+    PoolRatioPrecision(Address),
+    PoolVestingSchedule(Address),
+    PoolCreation(Address),
+    PoolBlocksPerDay(Address),
+    PoolAvailableSelfStake(Address),
+}
+
+impl PlannedRead {
+    /// StakingPool reads may fail individually; token reads must always succeed.
+    pub fn allow_failure(self) -> bool {
+        !matches!(self, Self::TotalSupply | Self::BurnBalance | Self::ExcludedBalance(_) | Self::ScheduleWalletBalance(_) | Self::ReservedPoolBalance(_))
+    }
+
+    /// The StakingPool this read targets, if any.
+    pub fn pool(self) -> Option<Address> {
+        match self {
+            Self::TotalSupply | Self::BurnBalance | Self::ExcludedBalance(_) | Self::ScheduleWalletBalance(_) | Self::ReservedPoolBalance(_) => None,
+            Self::PoolInitialStake(addr)
+            | Self::PoolLockPeriod(addr)
+            | Self::PoolVestingDuration(addr)
+            | Self::PoolRatioPrecision(addr)
+            | Self::PoolVestingSchedule(addr)
+            | Self::PoolCreation(addr)
+            | Self::PoolBlocksPerDay(addr)
+            | Self::PoolAvailableSelfStake(addr) => Some(addr),
+        }
+    }
+
+    /// Number of uint256 values the call returns.
+    fn output_count(self) -> usize {
+        if matches!(self, Self::PoolVestingSchedule(_)) { 2 } else { 1 }
+    }
+
+    /// ABI name of the method this read calls.
+    pub fn function(self) -> &'static str {
+        match self {
+            Self::TotalSupply => "totalSupply",
+            Self::BurnBalance | Self::ExcludedBalance(_) | Self::ScheduleWalletBalance(_) | Self::ReservedPoolBalance(_) => "balanceOf",
+            Self::PoolInitialStake(_) => "initialSelfStakeAmount",
+            Self::PoolLockPeriod(_) => "initialLockPeriod",
+            Self::PoolVestingDuration(_) => "vestingDuration",
+            Self::PoolRatioPrecision(_) => "RATIO_PRECISION",
+            Self::PoolVestingSchedule(_) => "getVestingSchedule",
+            Self::PoolCreation(_) => "poolCreation",
+            Self::PoolBlocksPerDay(_) => "BLOCKS_PER_DAY",
+            Self::PoolAvailableSelfStake(_) => "getAvailableSelfStake",
+        }
+    }
+
+    pub fn call<M: Middleware + 'static>(self, token: &ERC20<M>) -> Result<ContractCall<M, Token>, AbiError> {
+        match self {
+            Self::TotalSupply => token.method(self.function(), ()),
+            Self::BurnBalance => token.method(self.function(), Address::zero()),
+            Self::ExcludedBalance(addr) | Self::ScheduleWalletBalance(addr) | Self::ReservedPoolBalance(addr) => token.method(self.function(), addr),
+            Self::PoolInitialStake(addr)
+            | Self::PoolLockPeriod(addr)
+            | Self::PoolVestingDuration(addr)
+            | Self::PoolRatioPrecision(addr)
+            | Self::PoolVestingSchedule(addr)
+            | Self::PoolCreation(addr)
+            | Self::PoolBlocksPerDay(addr)
+            | Self::PoolAvailableSelfStake(addr) => StakingPool::new(addr, token.client()).method(self.function(), ()),
+        }
+    }
+}
+
+impl fmt::Display for PlannedRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TotalSupply => write!(f, "total supply"),
+            Self::BurnBalance => write!(f, "burn balance"),
+            Self::ExcludedBalance(addr) => write!(f, "excluded balance of {:?}", addr),
+            Self::ScheduleWalletBalance(addr) => write!(f, "balance of vesting wallet {:?}", addr),
+            Self::ReservedPoolBalance(addr) => write!(f, "balance of reserved pool {:?}", addr),
+            Self::PoolInitialStake(addr) => write!(f, "initial stake of pool {:?}", addr),
+            Self::PoolLockPeriod(addr) => write!(f, "lock period of pool {:?}", addr),
+            Self::PoolVestingDuration(addr) => write!(f, "vesting duration of pool {:?}", addr),
+            Self::PoolRatioPrecision(addr) => write!(f, "ratio precision of pool {:?}", addr),
+            Self::PoolVestingSchedule(addr) => write!(f, "vesting schedule of pool {:?}", addr),
+            Self::PoolCreation(addr) => write!(f, "creation block of pool {:?}", addr),
+            Self::PoolBlocksPerDay(addr) => write!(f, "blocks per day of pool {:?}", addr),
+            Self::PoolAvailableSelfStake(addr) => write!(f, "available self stake of pool {:?}", addr),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The multicall returned a different number of results than calls were planned.
+    LengthMismatch { expected: usize, actual: usize },
+    /// A read that is not allowed to fail did.
+    CallFailed { read: PlannedRead, failure: CallFailure },
+    /// A result could not be decoded into the expected type.
+    UnexpectedType { read: PlannedRead, token: Token },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch { expected, actual } => write!(f, "expected {} multicall results, got {}", expected, actual),
+            Self::CallFailed { read, failure } => write!(f, "failed to read {}: {}", read, failure),
+            Self::UnexpectedType { read, token } => write!(f, "unexpected value for {}: {:?}", read, token),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Raw StakingPool readings. Values whose call failed are left at zero and listed in `failures`;
+/// which values are populated depends on the [`PoolVestingMode`] the plan was built for.
+#[derive(Debug, Clone, Default)]
+pub struct PoolReading {
+    pub address: Address,
+    pub reserved: bool,
+    pub balance: U256,
+    pub initial_stake: U256,
+    pub lock_period: U256,
+    pub vesting_duration: U256,
+    pub ratio_precision: U256,
+    pub lock_days: U256,
+    pub vesting_days: U256,
+    pub pool_creation: U256,
+    pub blocks_per_day: U256,
+    /// Only read when cross-checking; a failure here does not invalidate the pool's schedule.
+    pub available_self_stake: Option<Result<U256, CallFailure>>,
+    pub failures: Vec<CallFailure>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SupplyReadings {
+    pub total_supply: U256,
+    pub burn_balance: U256,
+    pub excluded_balances: Vec<(Address, U256)>,
+    pub schedule_balances: Vec<(Address, U256)>,
+    pub pools: Vec<PoolReading>,
+}
+
+impl SupplyReadings {
+    fn pool_mut(&mut self, address: Address, reserved: bool) -> &mut PoolReading {
+        // Reads for one pool are planned contiguously and every pool is planned once, so only the
+        // last entry needs checking
+        if self.pools.last().is_none_or(|pool| pool.address != address) {
+            self.pools.push(PoolReading { address, reserved, ..Default::default() });
+        }
+        self.pools.last_mut().expect("pool was just pushed")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SupplyCallPlan {
+    reads: Vec<PlannedRead>,
+}
+
+impl SupplyCallPlan {
+    pub fn new(excluded_addresses: &[Address], schedule_wallets: &[Address], onchain_pool_addresses: &[(Address, bool)], mode: PoolVestingMode, cross_check: bool) -> Self {
+        let mut reads = vec![PlannedRead::TotalSupply, PlannedRead::BurnBalance];
+        reads.extend(excluded_addresses.iter().map(|&addr| PlannedRead::ExcludedBalance(addr)));
+        reads.extend(schedule_wallets.iter().map(|&addr| PlannedRead::ScheduleWalletBalance(addr)));
+        // A pool listed twice would otherwise have both entries' reads merged into one reading
+        let mut planned_pools: Vec<Address> = Vec::new();
+        for &(addr, reserved) in onchain_pool_addresses {
+            if planned_pools.contains(&addr) {
+                eprintln!("Ignoring duplicate pool entry {:?}", addr);
+                continue;
+            }
+            planned_pools.push(addr);
+            if reserved {
+                reads.push(PlannedRead::ReservedPoolBalance(addr));
+                continue;
+            }
+            match mode {
+                PoolVestingMode::Replay => reads.extend([
+                    PlannedRead::PoolInitialStake(addr),
+                    PlannedRead::PoolLockPeriod(addr),
+                    PlannedRead::PoolVestingDuration(addr),
+                    PlannedRead::PoolRatioPrecision(addr),
+                ]),
+                PoolVestingMode::Contract => reads.extend([
+                    PlannedRead::PoolInitialStake(addr),
+                    PlannedRead::PoolVestingSchedule(addr),
+                    PlannedRead::PoolCreation(addr),
+                    PlannedRead::PoolBlocksPerDay(addr),
+                    PlannedRead::PoolRatioPrecision(addr),
+                ]),
+            }
+            if cross_check {
+                reads.push(PlannedRead::PoolAvailableSelfStake(addr));
+            }
+        }
+        Self { reads }
+    }
+
+    pub fn reads(&self) -> &[PlannedRead] {
+        &self.reads
+    }
+
+    pub fn calls<M: Middleware + 'static>(&self, token: &ERC20<M>) -> Result<Vec<FallibleCall<M, Token>>, AbiError> {
+        self.reads.iter().map(|read| Ok((read.call(token)?, read.allow_failure()))).collect()
+    }
+
+    pub fn decode(&self, results: Vec<Result<Token, CallFailure>>) -> Result<SupplyReadings, DecodeError> {
+        if results.len() != self.reads.len() {
+            return Err(DecodeError::LengthMismatch { expected: self.reads.len(), actual: results.len() });
+        }
+
+        let mut readings = SupplyReadings::default();
+        for (&read, result) in self.reads.iter().zip(results) {
+            let values = match result {
+                // A pool answering with the wrong shape is handled like a pool whose read failed
+                Ok(token) => match (decode_uints(read, token), read.pool()) {
+                    (Ok(values), _) => Ok(values),
+                    (Err(_), Some(target)) => Err(CallFailure { target, function: read.function().to_string(), reason: "unexpected return value".to_string() }),
+                    (Err(e), None) => return Err(e),
+                },
+                Err(failure) if read.allow_failure() => Err(failure),
+                Err(failure) => return Err(DecodeError::CallFailed { read, failure }),
+            };
+
+            let Some(addr) = read.pool() else {
+                // Token reads never fail past this point, so the default is unreachable
+                let value = values.map(|values| values[0]).unwrap_or_default();
+                match read {
+                    PlannedRead::TotalSupply => readings.total_supply = value,
+                    PlannedRead::BurnBalance => readings.burn_balance = value,
+                    PlannedRead::ExcludedBalance(addr) => readings.excluded_balances.push((addr, value)),
+                    PlannedRead::ScheduleWalletBalance(addr) => readings.schedule_balances.push((addr, value)),
+                    PlannedRead::ReservedPoolBalance(addr) => readings.pool_mut(addr, true).balance = value,
+                    _ => unreachable!("pool reads are handled below"),
+                }
+                continue;
+            };
+
+            let pool = readings.pool_mut(addr, false);
+            if let PlannedRead::PoolAvailableSelfStake(_) = read {
+                pool.available_self_stake = Some(values.map(|values| values[0]));
+                continue;
+            }
+            match values {
+                Err(failure) => pool.failures.push(failure),
+                Ok(values) => match read {
+                    PlannedRead::PoolInitialStake(_) => pool.initial_stake = values[0],
+                    PlannedRead::PoolLockPeriod(_) => pool.lock_period = values[0],
+                    PlannedRead::PoolVestingDuration(_) => pool.vesting_duration = values[0],
+                    PlannedRead::PoolRatioPrecision(_) => pool.ratio_precision = values[0],
+                    PlannedRead::PoolVestingSchedule(_) => {
+                        pool.lock_days = values[0];
+                        pool.vesting_days = values[1];
+                    }
+                    PlannedRead::PoolCreation(_) => pool.pool_creation = values[0],
+                    PlannedRead::PoolBlocksPerDay(_) => pool.blocks_per_day = values[0],
+                    _ => unreachable!("token reads are handled above"),
+                },
+            }
+        }
+
+        Ok(readings)
+    }
+}
+
+fn decode_uints(read: PlannedRead, token: Token) -> Result<Vec<U256>, DecodeError> {
+    let tokens = match token {
+        Token::Tuple(tokens) => tokens,
+        token => vec![token],
+    };
+    let values: Option<Vec<U256>> = tokens.iter().cloned().map(Token::into_uint).collect();
+    match values {
+        Some(values) if values.len() == read.output_count() => Ok(values),
+        _ => Err(DecodeError::UnexpectedType { read, token: Token::Tuple(tokens) }),
+    }
+}
@@ -1,16 +1,167 @@
+use crate::supply::ReleaseGranularity;
+use crate::vesting::PIECEWISE_PRECISION;
 use ethers::types::{Address, U256};
 use serde_json;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone)]
+pub struct PoolSchedule {
+    pub addresses: Vec<(Address, String)>,
+    pub tge_percentage: U256,
+    pub cliff: U256,
+    pub vesting: U256,
+    pub vesting_type: String,
+    pub balance_at_tge: U256,
+    pub release_granularity: ReleaseGranularity,
+    /// Unlock tranches of a `custom` schedule, in the order listed.
+    pub tranches: Vec<TranchePoint>,
+    /// When vesting starts, as a unix timestamp. `None` starts at TGE.
+    pub start_timestamp: Option<U256>,
+    pub label: AllocationLabel,
+}
+
+/// Name and tokenomics category (team, investors, ecosystem, treasury, liquidity, ...) of an
+/// allocation. Both are optional in the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AllocationLabel {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+// Category reported for allocations that have none
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+impl AllocationLabel {
+    pub fn category(&self) -> &str {
+        self.category.as_deref().unwrap_or(UNCATEGORIZED)
+    }
+}
+
+/// An address whose balance is excluded from circulating supply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludedAddress {
+    pub address: Address,
+    pub chain: String,
+    pub label: AllocationLabel,
+}
+
+/// When a tranche unlocks: a number of days after the schedule starts, or a calendar date
+/// (UTC midnight, as a unix timestamp).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrancheTime {
+    Day(u64),
+    Date(u64),
+}
+
+impl TrancheTime {
+    /// Seconds after `start`. Dates before the start count as the start itself.
+    pub fn offset(&self, start: U256) -> U256 {
+        match self {
+            TrancheTime::Day(day) => U256::from(*day).saturating_mul(U256::from(SECONDS_PER_DAY)),
+            TrancheTime::Date(timestamp) => U256::from(*timestamp).saturating_sub(start),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranchePoint {
+    pub at: TrancheTime,
+    /// Share unlocked once this tranche is reached, in parts per million.
+    pub cumulative: U256,
+}
+
+impl PoolSchedule {
+    /// The schedule's own start, or TGE if it has none.
+    pub fn start(&self, tge_timestamp: U256) -> U256 {
+        self.start_timestamp.unwrap_or(tge_timestamp)
+    }
+
+    /// `(seconds after the start, cumulative share)` for each tranche, with dates resolved
+    /// against the start.
+    pub fn tranche_offsets(&self, tge_timestamp: U256) -> Vec<(U256, U256)> {
+        let start = self.start(tge_timestamp);
+        self.tranches.iter().map(|point| (point.at.offset(start), point.cumulative)).collect()
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 86_400;
 
 #[derive(Serialize, Deserialize)]
 struct PoolEntry {
     addresses: Vec<AddressInfo>,
+    #[serde(default)]
     tge_percentage: u64,
+    #[serde(default)]
     cliff: u64,
+    #[serde(default)]
     vesting: u64,
     balance_at_tge: u64,
     #[serde(default = "default_vesting_type")]
     vesting_type: String,
+    #[serde(default)]
+    release_granularity: ReleaseGranularity,
+    #[serde(default)]
+    tranches: Vec<TrancheEntry>,
+    #[serde(default)]
+    start_timestamp: Option<u64>,
+    #[serde(flatten)]
+    label: AllocationLabel,
+}
+
+/// A tranche is listed with exactly one of `date` (`YYYY-MM-DD`) or `day` (days after the
+/// schedule's start), and the cumulative percentage unlocked from then on.
+#[derive(Serialize, Deserialize)]
+struct TrancheEntry {
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    day: Option<u64>,
+    percentage: f64,
+}
+
+impl TrancheEntry {
+    fn to_point(&self) -> Result<TranchePoint, String> {
+        let at = match (&self.date, self.day) {
+            (Some(date), None) => TrancheTime::Date(parse_date(date).ok_or_else(|| format!("Invalid tranche date '{}', expected YYYY-MM-DD", date))?),
+            (None, Some(day)) => TrancheTime::Day(day),
+            _ => return Err("Each tranche needs exactly one of 'date' or 'day'".to_string()),
+        };
+        if !(0.0..=100.0).contains(&self.percentage) {
+            return Err(format!("Tranche percentage {} is outside 0-100", self.percentage));
+        }
+        // Percentages keep four decimal places
+        let cumulative = U256::from((self.percentage * (PIECEWISE_PRECISION / 100) as f64).round() as u64);
+        Ok(TranchePoint { at, cumulative })
+    }
+}
+
+/// Unix timestamp of UTC midnight on a `YYYY-MM-DD` date.
+fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if year < 1970 || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, counting years from March
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days as u64 * SECONDS_PER_DAY)
 }
 
 fn default_vesting_type() -> String {
@@ -23,6 +174,14 @@ struct AddressInfo {
     chain: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct ExcludedEntry {
+    address: Address,
+    chain: String,
+    #[serde(flatten)]
+    label: AllocationLabel,
+}
+
 #[derive(Serialize, Deserialize)]
 struct OnchainPoolEntry {
     address: Address,
@@ -30,38 +189,59 @@ struct OnchainPoolEntry {
     reserved: bool,
 }
 
-pub fn read_excluded_addresses() -> Vec<(Address, String)> {
+pub fn read_excluded_addresses() -> Vec<ExcludedAddress> {
     let content = include_str!("../config/excluded_addresses.json");
-    let entries: Vec<AddressInfo> = serde_json::from_str(&content).expect("Failed to parse excluded address list");
-    entries.into_iter().map(|info| (info.address, info.chain)).collect()
+    let entries: Vec<ExcludedEntry> = serde_json::from_str(content).expect("Failed to parse excluded address list");
+    entries.into_iter().map(|entry| ExcludedAddress { address: entry.address, chain: entry.chain, label: entry.label }).collect()
+}
+
+pub fn read_pool_data() -> Vec<PoolSchedule> {
+    parse_pool_data(include_str!("../config/excluded_address_list.json")).expect("Failed to parse pool address list")
 }
 
-pub fn read_pool_data() -> Vec<(Vec<(Address, String)>, U256, U256, U256, String, U256)> {
-    let content = include_str!("../config/excluded_address_list.json");
-    let pool_entries: Vec<PoolEntry> = serde_json::from_str(&content).expect("Failed to parse pool address list");
+/// Parses schedules in the `excluded_address_list.json` format.
+pub fn parse_pool_data(content: &str) -> Result<Vec<PoolSchedule>, String> {
+    let pool_entries: Vec<PoolEntry> = serde_json::from_str(content).map_err(|e| e.to_string())?;
     pool_entries
         .into_iter()
-        .map(|entry| (
-            entry.addresses.into_iter().map(|info| (info.address, info.chain)).collect(),
-            U256::from(entry.tge_percentage),
-            U256::from(entry.cliff),
-            U256::from(entry.vesting),
-            entry.vesting_type,
-            U256::from(entry.balance_at_tge) * U256::from(10u64.pow(18)), // Convert to wei
-        ))
+        .map(|entry| {
+            Ok(PoolSchedule {
+                addresses: entry.addresses.into_iter().map(|info| (info.address, info.chain)).collect(),
+                tge_percentage: U256::from(entry.tge_percentage),
+                cliff: U256::from(entry.cliff),
+                vesting: U256::from(entry.vesting),
+                vesting_type: entry.vesting_type,
+                balance_at_tge: U256::from(entry.balance_at_tge) * U256::from(10u64.pow(18)), // Convert to wei
+                release_granularity: entry.release_granularity,
+                tranches: entry.tranches.iter().map(TrancheEntry::to_point).collect::<Result<_, _>>()?,
+                start_timestamp: entry.start_timestamp.map(U256::from),
+                label: entry.label,
+            })
+        })
         .collect()
 }
 
+/// Matchain wallets of every schedule, deduplicated in the order they are listed.
+pub fn matchain_schedule_wallets(pool_data: &[PoolSchedule]) -> Vec<Address> {
+    let mut wallets: Vec<Address> = Vec::new();
+    for (addr, chain) in pool_data.iter().flat_map(|schedule| schedule.addresses.iter()) {
+        if chain == "Matchain" && !wallets.contains(addr) {
+            wallets.push(*addr);
+        }
+    }
+    wallets
+}
+
 pub fn read_onchain_pool_addresses() -> Vec<(Address, bool)> {
     let content = include_str!("../config/pool_addresses.json");
-    let entries: Vec<OnchainPoolEntry> = serde_json::from_str(&content).expect("Failed to parse onchain pool address list");
+    let entries: Vec<OnchainPoolEntry> = serde_json::from_str(content).expect("Failed to parse onchain pool address list");
     entries.into_iter().map(|entry| (entry.address, entry.reserved)).collect()
 }
 
 pub fn validate_address_lists() -> Result<(), String> {
     let excluded_addresses = read_excluded_addresses()
         .into_iter()
-        .map(|(addr, _)| addr)
+        .map(|excluded| excluded.address)
         .collect::<Vec<_>>();
     let onchain_pool_addresses = read_onchain_pool_addresses().into_iter().map(|(addr, _)| addr).collect::<Vec<_>>();
 
@@ -88,4 +268,43 @@ pub fn validate_address_lists() -> Result<(), String> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Checks that every `custom` schedule lists tranches in time order with non-decreasing
+/// percentages ending at 100%, and that no other schedule lists tranches.
+pub fn validate_pool_data(pool_data: &[PoolSchedule], tge_timestamp: U256) -> Result<(), String> {
+    for (i, schedule) in pool_data.iter().enumerate() {
+        let result = match schedule.vesting_type.as_str() {
+            // Flooring to whole days would unlock a dated tranche up to a day late
+            "custom" if schedule.release_granularity == ReleaseGranularity::Daily => Err("'daily' release granularity does not apply to 'custom' schedules; each tranche sets its own unlock time".to_string()),
+            "custom" => validate_tranches(&schedule.tranche_offsets(tge_timestamp)),
+            _ if !schedule.tranches.is_empty() => Err(format!("tranches are only used by the 'custom' vesting type, not '{}'", schedule.vesting_type)),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            let address = schedule.addresses.first().map(|(addr, _)| format!(" (0x{:x})", addr)).unwrap_or_default();
+            return Err(format!("Invalid vesting schedule #{}{} in 'config/excluded_address_list.json': {}", i, address, e));
+        }
+    }
+    Ok(())
+}
+
+/// Checks resolved `(seconds after the start, cumulative share)` tranches.
+pub fn validate_tranches(tranches: &[(U256, U256)]) -> Result<(), String> {
+    let Some(&(_, last)) = tranches.last() else {
+        return Err("a custom schedule needs at least one tranche".to_string());
+    };
+    for (i, pair) in tranches.windows(2).enumerate() {
+        let ((offset, cumulative), (next_offset, next_cumulative)) = (pair[0], pair[1]);
+        if next_offset <= offset {
+            return Err(format!("tranche #{} ({}s after the start) does not come after the tranche before it ({}s)", i + 1, next_offset, offset));
+        }
+        if next_cumulative < cumulative {
+            return Err(format!("tranche #{} unlocks less than the tranche before it", i + 1));
+        }
+    }
+    if last != U256::from(PIECEWISE_PRECISION) {
+        return Err("the last tranche must unlock 100%".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+// src/discovery.rs
+use crate::config::ExcludedAddress;
+use crate::utils;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, H256, Log};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CREATION_EVENT: &str = "PoolCreated(address)";
+// Stays under the log range limit most public RPCs enforce on eth_getLogs
+pub const DEFAULT_BLOCK_RANGE: u64 = 5_000;
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Factory whose creation events announce new pools.
+    pub factory: Option<Address>,
+    /// Signature of the factory's creation event, e.g. `PoolCreated(address)`.
+    pub creation_event: String,
+    /// keccak256 of the StakingPool runtime bytecode. Used to verify factory candidates, or on
+    /// its own to find pools among every contract created in the scanned blocks.
+    pub code_hash: Option<H256>,
+    pub from_block: u64,
+    pub block_range: u64,
+    /// How many blocks the scan stays behind the head, so pools created in reorged blocks are
+    /// not recorded.
+    pub confirmations: u64,
+    pub state_path: PathBuf,
+}
+
+impl DiscoveryConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.factory.is_some() || self.code_hash.is_some()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        if self.factory.is_some() { DiscoverySource::FactoryEvent } else { DiscoverySource::CodeHash }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoverySource {
+    FactoryEvent,
+    CodeHash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredPool {
+    pub address: Address,
+    pub block: u64,
+    pub source: DiscoverySource,
+}
+
+/// Pools found so far and how far the scan got, persisted between runs so a restart resumes
+/// where the last scan stopped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveredPools {
+    pub last_scanned_block: Option<u64>,
+    pub pools: Vec<DiscoveredPool>,
+}
+
+impl DiscoveredPools {
+    /// Loads the state file, starting empty if it does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        utils::load_json_state(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        utils::save_json_state(self, path)
+    }
+
+    fn contains(&self, address: Address) -> bool {
+        self.pools.iter().any(|pool| pool.address == address)
+    }
+}
+
+/// Scans from the block after `state.last_scanned_block` (or `from_block`) up to the confirmed head
+/// in windows of `block_range`, appending new pools to `state` and saving it after every window.
+/// Returns the number of pools found.
+pub async fn discover<M: Middleware + 'static>(client: &M, config: &DiscoveryConfig, state: &mut DiscoveredPools) -> Result<usize, anyhow::Error> {
+    let head = client.get_block_number().await?.as_u64().saturating_sub(config.confirmations);
+    let mut from = state.last_scanned_block.map_or(config.from_block, |block| block + 1);
+    let mut found = 0;
+
+    while from <= head {
+        let to = head.min(from.saturating_add(config.block_range.max(1) - 1));
+        let candidates = match config.factory {
+            Some(factory) => factory_candidates(client, factory, &config.creation_event, from, to).await?,
+            None => created_contracts(client, from, to).await?,
+        };
+
+        for (address, block) in candidates {
+            if state.contains(address) {
+                continue;
+            }
+            if let Some(code_hash) = config.code_hash
+                && H256(keccak256(client.get_code(address, None).await?)) != code_hash
+            {
+                continue;
+            }
+            eprintln!("Discovered staking pool {:?} at block {}", address, block);
+            state.pools.push(DiscoveredPool { address, block, source: config.source() });
+            found += 1;
+        }
+
+        state.last_scanned_block = Some(to);
+        state.save(&config.state_path)?;
+        from = to + 1;
+    }
+
+    Ok(found)
+}
+
+async fn factory_candidates<M: Middleware + 'static>(client: &M, factory: Address, event: &str, from: u64, to: u64) -> Result<Vec<(Address, u64)>, anyhow::Error> {
+    let filter = Filter::new().address(factory).event(event).from_block(from).to_block(to);
+    let logs = client.get_logs(&filter).await?;
+    Ok(logs.iter().filter_map(|log| Some((created_address(log)?, log.block_number?.as_u64()))).collect())
+}
+
+/// The pool address is the first indexed argument if there is one, otherwise the first data word.
+fn created_address(log: &Log) -> Option<Address> {
+    let word = match log.topics.get(1) {
+        Some(topic) => topic.as_bytes(),
+        None => log.data.get(..32)?,
+    };
+    Some(Address::from_slice(&word[12..]))
+}
+
+/// Contracts deployed directly by a transaction in `from..=to`. Pools deployed from inside
+/// another contract do not show up in receipts; use a factory for those.
+async fn created_contracts<M: Middleware + 'static>(client: &M, from: u64, to: u64) -> Result<Vec<(Address, u64)>, anyhow::Error> {
+    let mut created = Vec::new();
+    for block in from..=to {
+        let receipts = client.get_block_receipts(block).await?;
+        created.extend(receipts.iter().filter_map(|receipt| receipt.contract_address).map(|address| (address, block)));
+    }
+    Ok(created)
+}
+
+/// Manual entries come first and keep their `reserved` flag. Discovered pools that are not
+/// listed manually are appended as unreserved; any on the excluded list are dropped, since they
+/// would otherwise be counted twice.
+pub fn merge_pools(manual: &[(Address, bool)], discovered: &DiscoveredPools, excluded: &[ExcludedAddress]) -> Vec<(Address, bool)> {
+    let mut pools = manual.to_vec();
+    for pool in &discovered.pools {
+        if pools.iter().any(|&(address, _)| address == pool.address) {
+            continue;
+        }
+        if excluded.iter().any(|excluded| excluded.address == pool.address) {
+            eprintln!("Ignoring discovered pool {:?}: it is in the excluded address list", pool.address);
+            continue;
+        }
+        pools.push((pool.address, false));
+    }
+    pools
+}
@@ -0,0 +1,299 @@
+// src/indexer.rs
+use crate::{StakingPoolEvents, utils};
+use ethers::contract::EthLogDecode;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, H256, Log, U256};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+// Blocks behind the head the indexer stays, so reorged-out logs are never stored
+pub const DEFAULT_CONFIRMATIONS: u64 = 20;
+
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    pub from_block: u64,
+    pub block_range: u64,
+    pub confirmations: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StakingEventKind {
+    Staked { staker: Address, amount: U256 },
+    Unstaked { staker: Address, amount: U256 },
+    Claimed { staker: Address, amount: U256 },
+    Reward { amount: U256 },
+    FeeChanged { old_fee: U256, new_fee: U256 },
+    OwnershipTransferred { previous_owner: Address, new_owner: Address },
+}
+
+impl StakingEventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Staked { .. } => "staked",
+            Self::Unstaked { .. } => "unstaked",
+            Self::Claimed { .. } => "claimed",
+            Self::Reward { .. } => "reward",
+            Self::FeeChanged { .. } => "fee_changed",
+            Self::OwnershipTransferred { .. } => "ownership_transferred",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StakingEvent {
+    pub pool: Address,
+    pub block: u64,
+    pub transaction_hash: H256,
+    pub log_index: u64,
+    #[serde(flatten)]
+    pub kind: StakingEventKind,
+}
+
+impl StakingEvent {
+    /// Decodes a StakingPool log, returning `None` for events that are not indexed.
+    fn from_log(log: &Log) -> Option<Self> {
+        let kind = match StakingPoolEvents::decode_log(&log.clone().into()).ok()? {
+            StakingPoolEvents::StakedFilter(e) => StakingEventKind::Staked { staker: e.staker, amount: e.amount },
+            StakingPoolEvents::UnstakedFilter(e) => StakingEventKind::Unstaked { staker: e.staker, amount: e.amount },
+            StakingPoolEvents::ClaimedFilter(e) => StakingEventKind::Claimed { staker: e.staker, amount: e.amount },
+            StakingPoolEvents::RewardFilter(e) => StakingEventKind::Reward { amount: e.amount },
+            StakingPoolEvents::FeeChangedFilter(e) => StakingEventKind::FeeChanged { old_fee: e.old_fee, new_fee: e.new_fee },
+            StakingPoolEvents::OwnershipTransferredFilter(e) => StakingEventKind::OwnershipTransferred { previous_owner: e.previous_owner, new_owner: e.new_owner },
+            StakingPoolEvents::InitializedFilter(_) => return None,
+        };
+        Some(Self {
+            pool: log.address,
+            block: log.block_number?.as_u64(),
+            transaction_hash: log.transaction_hash?,
+            log_index: log.log_index?.as_u64(),
+            kind,
+        })
+    }
+
+    /// Whether `account` is the staker of this event, or one side of an ownership transfer.
+    fn involves(&self, account: Address) -> bool {
+        match self.kind {
+            StakingEventKind::Staked { staker, .. } | StakingEventKind::Unstaked { staker, .. } | StakingEventKind::Claimed { staker, .. } => staker == account,
+            StakingEventKind::OwnershipTransferred { previous_owner, new_owner } => previous_owner == account || new_owner == account,
+            StakingEventKind::Reward { .. } | StakingEventKind::FeeChanged { .. } => false,
+        }
+    }
+
+    pub fn to_info(&self, decimals: u8) -> StakingEventInfo {
+        let mut info = StakingEventInfo {
+            pool: self.pool,
+            block: self.block,
+            transaction_hash: self.transaction_hash,
+            log_index: self.log_index,
+            event: self.kind.name(),
+            staker: None,
+            amount: None,
+            old_fee: None,
+            new_fee: None,
+            previous_owner: None,
+            new_owner: None,
+        };
+        match self.kind {
+            StakingEventKind::Staked { staker, amount } | StakingEventKind::Unstaked { staker, amount } | StakingEventKind::Claimed { staker, amount } => {
+                info.staker = Some(staker);
+                info.amount = Some(utils::u256_to_human(amount, decimals));
+            }
+            StakingEventKind::Reward { amount } => info.amount = Some(utils::u256_to_human(amount, decimals)),
+            StakingEventKind::FeeChanged { old_fee, new_fee } => {
+                info.old_fee = Some(utils::saturating_u64(old_fee));
+                info.new_fee = Some(utils::saturating_u64(new_fee));
+            }
+            StakingEventKind::OwnershipTransferred { previous_owner, new_owner } => {
+                info.previous_owner = Some(previous_owner);
+                info.new_owner = Some(new_owner);
+            }
+        }
+        info
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StakingEventInfo {
+    #[schema(value_type = String)]
+    pub pool: Address,
+    pub block: u64,
+    #[schema(value_type = String)]
+    pub transaction_hash: H256,
+    pub log_index: u64,
+    #[schema(value_type = String)]
+    pub event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub staker: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub previous_owner: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub new_owner: Option<Address>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EventHistory {
+    #[schema(value_type = String)]
+    pub address: Address,
+    /// Last block whose events are included; `None` if the relevant pools are not indexed yet.
+    pub indexed_to_block: Option<u64>,
+    pub events: Vec<StakingEventInfo>,
+}
+
+/// Indexed events, stored as an append-only JSON Lines file next to a file holding the last
+/// indexed block of every pool.
+#[derive(Debug)]
+pub struct EventStore {
+    events: Vec<StakingEvent>,
+    cursors: HashMap<Address, u64>,
+    events_path: PathBuf,
+    cursors_path: PathBuf,
+}
+
+impl EventStore {
+    /// Opens the store at `events_path`, starting empty if it does not exist yet.
+    pub fn open(events_path: &Path) -> Result<Self, anyhow::Error> {
+        let cursors_path = events_path.with_extension("cursors.json");
+        let cursors: HashMap<Address, u64> = utils::load_json_state(&cursors_path)?;
+
+        let content = match std::fs::read_to_string(events_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", events_path.display(), e)),
+        };
+        let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+        let mut events = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            let event: StakingEvent = match serde_json::from_str(line) {
+                Ok(event) => event,
+                // A crash mid-append can leave the last line truncated; its window is indexed again
+                Err(_) if i + 1 == lines.len() => continue,
+                Err(e) => return Err(anyhow::anyhow!("Failed to parse {}: {}", events_path.display(), e)),
+            };
+            // Events past a pool's cursor were appended before a crash and will be indexed again
+            if cursors.get(&event.pool).is_some_and(|&cursor| event.block <= cursor) {
+                events.push(event);
+            }
+        }
+        events.sort_by_key(|event| (event.block, event.log_index));
+
+        if events.len() != lines.len() {
+            // Rewrite without the dropped lines so re-indexing them does not store duplicates
+            let tmp = events_path.with_extension("jsonl.tmp");
+            let mut rewritten = String::new();
+            for event in &events {
+                rewritten.push_str(&serde_json::to_string(event)?);
+                rewritten.push('\n');
+            }
+            std::fs::write(&tmp, rewritten)?;
+            std::fs::rename(&tmp, events_path)?;
+        }
+
+        Ok(Self { events, cursors, events_path: events_path.to_path_buf(), cursors_path })
+    }
+
+    pub fn indexed_to(&self, pool: Address) -> Option<u64> {
+        self.cursors.get(&pool).copied()
+    }
+
+    /// Every account that has staked in each indexed pool, in order of first stake. Pools the
+    /// indexer has not reached yet are left out, since their stakers are unknown.
+    pub fn stakers(&self) -> HashMap<Address, Vec<Address>> {
+        let mut stakers: HashMap<Address, Vec<Address>> = self.cursors.keys().map(|&pool| (pool, Vec::new())).collect();
+        for event in &self.events {
+            if let (StakingEventKind::Staked { staker, .. }, Some(pool_stakers)) = (&event.kind, stakers.get_mut(&event.pool))
+                && !pool_stakers.contains(staker)
+            {
+                pool_stakers.push(*staker);
+            }
+        }
+        stakers
+    }
+
+    fn record(&mut self, pools: &[Address], to: u64, events: Vec<StakingEvent>) -> Result<(), anyhow::Error> {
+        if !events.is_empty() {
+            let mut file = OpenOptions::new().create(true).append(true).open(&self.events_path)?;
+            for event in &events {
+                writeln!(file, "{}", serde_json::to_string(event)?)?;
+            }
+            file.sync_data()?;
+        }
+
+        for &pool in pools {
+            self.cursors.insert(pool, to);
+        }
+        utils::save_json_state(&self.cursors, &self.cursors_path)?;
+
+        self.events.extend(events);
+        // Backfilled pools can land behind events already stored for other pools
+        self.events.sort_by_key(|event| (event.block, event.log_index));
+        Ok(())
+    }
+
+    pub fn pool_history(&self, pool: Address, from_block: Option<u64>, to_block: Option<u64>, decimals: u8) -> EventHistory {
+        EventHistory {
+            address: pool,
+            indexed_to_block: self.indexed_to(pool),
+            events: self.history(|event| event.pool == pool, from_block, to_block, decimals),
+        }
+    }
+
+    /// Events from every indexed pool that involve `staker`. `indexed_to_block` is the lowest
+    /// pool cursor, since a pool that is still backfilling may hold older events.
+    pub fn staker_history(&self, staker: Address, from_block: Option<u64>, to_block: Option<u64>, decimals: u8) -> EventHistory {
+        EventHistory {
+            address: staker,
+            indexed_to_block: self.cursors.values().min().copied(),
+            events: self.history(|event| event.involves(staker), from_block, to_block, decimals),
+        }
+    }
+
+    fn history(&self, matches: impl Fn(&StakingEvent) -> bool, from_block: Option<u64>, to_block: Option<u64>, decimals: u8) -> Vec<StakingEventInfo> {
+        self.events
+            .iter()
+            .filter(|event| from_block.is_none_or(|from| event.block >= from) && to_block.is_none_or(|to| event.block <= to))
+            .filter(|event| matches(event))
+            .map(|event| event.to_info(decimals))
+            .collect()
+    }
+}
+
+/// Indexes `pools` up to the confirmed head. Pools whose cursor is furthest behind are scanned
+/// first, each window stopping where the next group of pools resumes, so a newly added pool is
+/// backfilled on its own and then joins the others. Returns the number of events stored.
+pub async fn index<M: Middleware + 'static>(client: &M, config: &IndexerConfig, store: &RwLock<EventStore>, pools: &[Address]) -> Result<usize, anyhow::Error> {
+    let head = client.get_block_number().await?.as_u64().saturating_sub(config.confirmations);
+    let mut stored = 0;
+
+    loop {
+        let next_blocks: Vec<(Address, u64)> = {
+            let store = store.read().expect("event store lock poisoned");
+            pools.iter().map(|&pool| (pool, store.indexed_to(pool).map_or(config.from_block, |block| block + 1))).collect()
+        };
+        let Some(from) = next_blocks.iter().map(|&(_, next)| next).filter(|&next| next <= head).min() else {
+            return Ok(stored);
+        };
+        let behind: Vec<Address> = next_blocks.iter().filter(|&&(_, next)| next == from).map(|&(pool, _)| pool).collect();
+        let resume = next_blocks.iter().map(|&(_, next)| next).filter(|&next| next > from).min().unwrap_or(u64::MAX);
+        let to = head.min(from.saturating_add(config.block_range.max(1) - 1)).min(resume - 1);
+
+        let filter = Filter::new().address(behind.clone()).from_block(from).to_block(to);
+        let events: Vec<StakingEvent> = client.get_logs(&filter).await?.iter().filter_map(StakingEvent::from_log).collect();
+        stored += events.len();
+        store.write().expect("event store lock poisoned").record(&behind, to, events)?;
+    }
+}
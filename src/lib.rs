@@ -9,11 +9,25 @@ abigen!(
     function totalSupply() external view returns (uint256)
     function balanceOf(address) external view returns (uint256)
     function decimals() external view returns (uint8)
+    function cap() external view returns (uint256)
 ]"#
 );
 
 abigen!(StakingPool, "abi/staking_pool_abi.json");
 
+pub mod aggregators;
+pub mod alerts;
+pub mod allocations;
+pub mod call_plan;
 pub mod config;
+pub mod discovery;
+pub mod indexer;
+pub mod monitor;
+pub mod multicall;
+pub mod openapi;
+pub mod staking;
 pub mod supply;
 pub mod utils;
+pub mod webhook;
+
+pub use matchain_vesting as vesting;
@@ -1,29 +1,82 @@
 use anyhow::Result as AnyhowResult;
-use axum::{Router, extract::State, routing::get};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Json, routing::get};
 use dotenvy::dotenv;
 use ethers::providers::{Http, Provider};
 use ethers::types::{Address, U256};
+use matchain_supply_apis::alerts::{self, Alert, AlertConfig, DEFAULT_FAILURE_THRESHOLD, DEFAULT_SUPPLY_CHANGE_BPS, SupplyWatcher};
+use matchain_supply_apis::allocations::{self, AllocationsReport, SupplyBasis};
+use matchain_supply_apis::aggregators::{self, CoinGeckoResult, SupplyFigure, SupplyFigures, SupplyValues};
+use matchain_supply_apis::config::{ExcludedAddress, PoolSchedule};
+use matchain_supply_apis::discovery::{self, DEFAULT_BLOCK_RANGE, DEFAULT_CREATION_EVENT, DiscoveredPools, DiscoveryConfig};
+use matchain_supply_apis::indexer::{self, DEFAULT_CONFIRMATIONS, EventHistory, EventStore, IndexerConfig};
+use matchain_supply_apis::monitor::{self, MonitorConfig, MonitorState, ViolationReport};
+use matchain_supply_apis::multicall::{self, DEFAULT_MAX_CALLS_PER_BATCH, MulticallConfig};
+use matchain_supply_apis::supply::{DEFAULT_BLOCK_TIME_MS, MaxSupply, PoolFailurePolicy, PoolVestingMode, ReleaseGranularity, StakedSupplyReport, SupplyBreakdown, SupplyOptions, TimeUnit, UnbondingTreatment};
+use matchain_supply_apis::staking::{self, PoolStakingInfo, StakingSummary};
+use matchain_supply_apis::utils::AmountFormat;
+use matchain_supply_apis::openapi::{self, ApiDoc};
+use matchain_supply_apis::webhook::{Dispatcher, Webhook, WebhookFormat};
 use matchain_supply_apis::{ERC20, config, supply};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::net::TcpListener;
 
 #[derive(Clone)]
 struct AppState {
     matchain_contract: Arc<ERC20<Provider<Http>>>,
     bsc_contract: Arc<ERC20<Provider<Http>>>,
-    excluded_addresses: Vec<(Address, String)>,
-    pool_data: Vec<(Vec<(Address, String)>, U256, U256, U256, String, U256)>,
-    onchain_pool_addresses: Vec<(Address, bool)>,
-    tge_timestamp: U256,
+    excluded_addresses: Vec<ExcludedAddress>,
+    pool_data: Vec<PoolSchedule>,
+    onchain_pool_addresses: Arc<RwLock<Vec<(Address, bool)>>>,
+    supply_options: SupplyOptions,
+    matchain_multicall: MulticallConfig,
+    bsc_multicall: MulticallConfig,
+    staking_events: Option<Arc<RwLock<EventStore>>>,
+    violations: Option<Arc<RwLock<MonitorState>>>,
+    max_supply: MaxSupply,
     decimals: u8,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HistoryQuery {
+    /// Only events at or after this block.
+    from_block: Option<u64>,
+    /// Only events at or before this block.
+    to_block: Option<u64>,
+}
+
+impl AppState {
+    fn pool_addresses(&self) -> Vec<(Address, bool)> {
+        self.onchain_pool_addresses.read().expect("pool address lock poisoned").clone()
+    }
+
+    /// Stakers of each pool seen by the staking indexer; empty when it is disabled.
+    fn known_stakers(&self) -> HashMap<Address, Vec<Address>> {
+        self.staking_events.as_ref().map(|store| store.read().expect("event store lock poisoned").stakers()).unwrap_or_default()
+    }
+}
+
 #[tokio::main]
 async fn main() -> AnyhowResult<()> {
     dotenv().ok();
 
+    // Prints the OpenAPI document without connecting to any RPC
+    if env::args().nth(1).as_deref() == Some("--openapi") {
+        println!("{}", api_router().into_openapi().to_pretty_json()?);
+        return Ok(());
+    }
+
     if let Err(e) = config::validate_address_lists() {
         eprintln!("Configuration Error: {}", e);
         std::process::exit(1);
@@ -34,6 +87,127 @@ async fn main() -> AnyhowResult<()> {
     let token_address = env::var("TOKEN_ADDRESS")?.parse::<Address>()?;
     let mat_bnb_token_address = env::var("MAT_BNB_TOKEN_ADDRESS")?.parse::<Address>()?;
     let tge_timestamp = U256::from(env::var("TGE_TIMESTAMP")?.parse::<u64>()?);
+    let pool_failure_policy = match env::var("POOL_FAILURE_POLICY") {
+        Ok(value) => value.parse::<PoolFailurePolicy>()?,
+        Err(_) => PoolFailurePolicy::Locked,
+    };
+    let pool_vesting_mode = match env::var("POOL_VESTING_MODE") {
+        Ok(value) => value.parse::<PoolVestingMode>()?,
+        Err(_) => PoolVestingMode::Replay,
+    };
+    let pool_cross_check = env::var("POOL_VESTING_CROSS_CHECK").is_ok_and(|value| value == "true" || value == "1");
+    let block_time_ms = match env::var("BLOCK_TIME_MS") {
+        Ok(value) => value.parse::<u64>()?,
+        Err(_) => DEFAULT_BLOCK_TIME_MS,
+    };
+    // Replayed pool periods are converted to days with this, so a day must hold at least one block
+    if (TimeUnit::Blocks { block_time_ms }).units_per_day().is_zero() {
+        return Err(anyhow::anyhow!("BLOCK_TIME_MS must be between 1 and 86400000, got {}", block_time_ms));
+    }
+    // StakingPool contracts release self-stake once per day (see getDailyVestingRate)
+    let pool_release_granularity = match env::var("POOL_RELEASE_GRANULARITY") {
+        Ok(value) => value.parse::<ReleaseGranularity>()?,
+        Err(_) => ReleaseGranularity::Daily,
+    };
+    let unbonding = match env::var("UNBONDING_SUPPLY") {
+        Ok(value) => value.parse::<UnbondingTreatment>()?,
+        Err(_) => UnbondingTreatment::Off,
+    };
+    let max_calls_per_batch = match env::var("MULTICALL_MAX_CALLS_PER_BATCH") {
+        Ok(value) => value.parse::<usize>()?,
+        Err(_) => DEFAULT_MAX_CALLS_PER_BATCH,
+    };
+    let pool_discovery_from_block = match env::var("POOL_DISCOVERY_FROM_BLOCK") {
+        Ok(value) => Some(value.parse::<u64>()?),
+        Err(_) => None,
+    };
+    let discovery_config = DiscoveryConfig {
+        factory: env::var("POOL_FACTORY_ADDRESS").ok().map(|value| value.parse::<Address>()).transpose()?,
+        creation_event: env::var("POOL_FACTORY_EVENT").unwrap_or_else(|_| DEFAULT_CREATION_EVENT.to_string()),
+        code_hash: env::var("POOL_CODE_HASH").ok().map(|value| value.parse()).transpose()?,
+        from_block: pool_discovery_from_block.unwrap_or(0),
+        block_range: match env::var("POOL_DISCOVERY_BLOCK_RANGE") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_BLOCK_RANGE,
+        },
+        confirmations: match env::var("POOL_DISCOVERY_CONFIRMATIONS") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_CONFIRMATIONS,
+        },
+        state_path: PathBuf::from(env::var("POOL_DISCOVERY_STATE_PATH").unwrap_or_else(|_| "discovered_pools.json".to_string())),
+    };
+    // A bytecode scan reads every block's receipts, so scanning from genesis by default would take days
+    if discovery_config.factory.is_none() && discovery_config.code_hash.is_some() && pool_discovery_from_block.is_none() {
+        return Err(anyhow::anyhow!("POOL_CODE_HASH without POOL_FACTORY_ADDRESS requires POOL_DISCOVERY_FROM_BLOCK"));
+    }
+    let discovery_interval = Duration::from_secs(match env::var("POOL_DISCOVERY_INTERVAL_SECS") {
+        Ok(value) => value.parse::<u64>()?,
+        Err(_) => 600,
+    });
+    let staking_indexer = env::var("STAKING_INDEXER").is_ok_and(|value| value == "true" || value == "1");
+    // Delegators' pending unstakes can only be read for stakers the indexer has seen
+    if unbonding != UnbondingTreatment::Off && !staking_indexer {
+        return Err(anyhow::anyhow!("UNBONDING_SUPPLY requires STAKING_INDEXER to be enabled"));
+    }
+    let indexer_config = IndexerConfig {
+        from_block: match env::var("STAKING_INDEXER_FROM_BLOCK") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => 0,
+        },
+        block_range: match env::var("STAKING_INDEXER_BLOCK_RANGE") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_BLOCK_RANGE,
+        },
+        confirmations: match env::var("STAKING_INDEXER_CONFIRMATIONS") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_CONFIRMATIONS,
+        },
+    };
+    let indexer_path = PathBuf::from(env::var("STAKING_INDEXER_PATH").unwrap_or_else(|_| "staking_events.jsonl".to_string()));
+    let indexer_interval = Duration::from_secs(match env::var("STAKING_INDEXER_INTERVAL_SECS") {
+        Ok(value) => value.parse::<u64>()?,
+        Err(_) => 30,
+    });
+    let outflow_monitor = env::var("OUTFLOW_MONITOR").is_ok_and(|value| value == "true" || value == "1");
+    let monitor_config = MonitorConfig {
+        from_block: match env::var("OUTFLOW_MONITOR_FROM_BLOCK") {
+            Ok(value) => Some(value.parse::<u64>()?),
+            Err(_) => None,
+        },
+        block_range: match env::var("OUTFLOW_MONITOR_BLOCK_RANGE") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_BLOCK_RANGE,
+        },
+        confirmations: match env::var("OUTFLOW_MONITOR_CONFIRMATIONS") {
+            Ok(value) => value.parse::<u64>()?,
+            Err(_) => DEFAULT_CONFIRMATIONS,
+        },
+        state_path: PathBuf::from(env::var("OUTFLOW_MONITOR_STATE_PATH").unwrap_or_else(|_| "outflow_monitor.json".to_string())),
+    };
+    let monitor_interval = Duration::from_secs(match env::var("OUTFLOW_MONITOR_INTERVAL_SECS") {
+        Ok(value) => value.parse::<u64>()?,
+        Err(_) => 60,
+    });
+    let webhook_secret = env::var("ALERT_WEBHOOK_SECRET").ok();
+    let alert_webhooks = Dispatcher::default()
+        .with(env::var("ALERT_WEBHOOK_URL").ok().map(|url| Webhook::new(url, WebhookFormat::Json).with_secret(webhook_secret.clone())))
+        .with(env::var("SLACK_WEBHOOK_URL").ok().map(|url| Webhook::new(url, WebhookFormat::Slack)))
+        .with(env::var("DISCORD_WEBHOOK_URL").ok().map(|url| Webhook::new(url, WebhookFormat::Discord)));
+    let violation_webhooks = alert_webhooks.clone().with(env::var("VIOLATION_WEBHOOK_URL").ok().map(|url| Webhook::new(url, WebhookFormat::Json).with_secret(webhook_secret.clone())));
+    let alert_config = AlertConfig {
+        supply_change_bps: match env::var("SUPPLY_CHANGE_ALERT_PERCENT") {
+            Ok(value) => alerts::parse_basis_points(&value)?,
+            Err(_) => DEFAULT_SUPPLY_CHANGE_BPS,
+        },
+        failure_threshold: match env::var("READ_FAILURE_ALERT_THRESHOLD") {
+            Ok(value) => value.parse::<u32>()?,
+            Err(_) => DEFAULT_FAILURE_THRESHOLD,
+        },
+    };
+    let refresh_interval = Duration::from_secs(match env::var("SUPPLY_REFRESH_INTERVAL_SECS") {
+        Ok(value) => value.parse::<u64>()?,
+        Err(_) => 60,
+    });
 
     let matchain_provider = Provider::<Http>::try_from(rpc_url)?;
     let bsc_provider = Provider::<Http>::try_from(bnb_rpc_url)?;
@@ -41,11 +215,70 @@ async fn main() -> AnyhowResult<()> {
     let matchain_contract = Arc::new(ERC20::new(token_address, Arc::new(matchain_provider)));
     let bsc_contract = Arc::new(ERC20::new(mat_bnb_token_address, Arc::new(bsc_provider)));
 
+    let matchain_multicall = MulticallConfig::default()
+        .with_address(multicall::parse_address_setting(env::var("MULTICALL_ADDRESS").ok())?)
+        .with_max_calls_per_batch(max_calls_per_batch)
+        .detect(matchain_contract.client().as_ref(), "Matchain")
+        .await?;
+    let bsc_multicall = MulticallConfig::default()
+        .with_address(multicall::parse_address_setting(env::var("BNB_MULTICALL_ADDRESS").ok())?)
+        .with_max_calls_per_batch(max_calls_per_batch)
+        .detect(bsc_contract.client().as_ref(), "BSC")
+        .await?;
+
     let decimals = matchain_contract.decimals().call().await?;
+    let max_supply_setting = match env::var("MAX_SUPPLY") {
+        Ok(value) => MaxSupply::parse(&value, decimals)?,
+        Err(_) => MaxSupply::Unset,
+    };
 
     let excluded_addresses = config::read_excluded_addresses();
     let pool_data = config::read_pool_data();
-    let onchain_pool_addresses = config::read_onchain_pool_addresses();
+    if let Err(e) = config::validate_pool_data(&pool_data, tge_timestamp) {
+        eprintln!("Configuration Error: {}", e);
+        std::process::exit(1);
+    }
+    let manual_pool_addresses = config::read_onchain_pool_addresses();
+    let discovered_pools = if discovery_config.is_enabled() { DiscoveredPools::load(&discovery_config.state_path)? } else { DiscoveredPools::default() };
+    let onchain_pool_addresses = Arc::new(RwLock::new(discovery::merge_pools(&manual_pool_addresses, &discovered_pools, &excluded_addresses)));
+
+    if discovery_config.is_enabled() {
+        tokio::spawn(run_pool_discovery(
+            matchain_contract.client(),
+            discovery_config,
+            discovered_pools,
+            manual_pool_addresses,
+            excluded_addresses.clone(),
+            onchain_pool_addresses.clone(),
+            discovery_interval,
+        ));
+    }
+
+    let staking_events = if staking_indexer {
+        let store = Arc::new(RwLock::new(EventStore::open(&indexer_path)?));
+        tokio::spawn(run_staking_indexer(matchain_contract.client(), indexer_config, store.clone(), onchain_pool_addresses.clone(), indexer_interval));
+        Some(store)
+    } else {
+        None
+    };
+
+    let violations = if outflow_monitor {
+        let monitor_state = Arc::new(RwLock::new(MonitorState::load(&monitor_config.state_path)?));
+        tokio::spawn(run_outflow_monitor(
+            matchain_contract.clone(),
+            pool_data.clone(),
+            tge_timestamp,
+            matchain_multicall,
+            monitor_config,
+            monitor_state.clone(),
+            violation_webhooks,
+            monitor_interval,
+            decimals,
+        ));
+        Some(monitor_state)
+    } else {
+        None
+    };
 
     let state = Arc::new(AppState {
         matchain_contract,
@@ -53,13 +286,32 @@ async fn main() -> AnyhowResult<()> {
         excluded_addresses,
         pool_data,
         onchain_pool_addresses,
-        tge_timestamp,
+        supply_options: SupplyOptions {
+            tge_timestamp,
+            pool_failure_policy,
+            pool_vesting_mode,
+            pool_cross_check,
+            block_time_ms,
+            pool_release_granularity,
+            unbonding,
+        },
+        matchain_multicall,
+        bsc_multicall,
+        staking_events,
+        violations,
+        max_supply: max_supply_setting,
         decimals,
     });
 
-    let app = Router::new()
-        .route("/total-supply", get(total_supply))
-        .route("/circulating-supply", get(circulating_supply))
+    if !alert_webhooks.is_empty() {
+        let watcher = SupplyWatcher::new(alert_config, state.pool_data.clone(), tge_timestamp, decimals);
+        tokio::spawn(run_supply_refresh(state.clone(), watcher, alert_webhooks, refresh_interval));
+    }
+
+    let (router, api) = api_router().split_for_parts();
+    let app = router
+        .route("/openapi.json", get(move || async move { Json(api) }))
+        .route("/docs", get(|| async { Html(openapi::DOCS_HTML) }))
         .with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
@@ -69,31 +321,396 @@ async fn main() -> AnyhowResult<()> {
     Ok(())
 }
 
-async fn total_supply(State(state): State<Arc<AppState>>) -> String {
-    match supply::get_total_supply(&state.matchain_contract, &state.bsc_contract, state.decimals).await {
+/// Every documented route, collecting its OpenAPI paths and schemas.
+fn api_router() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(total_supply))
+        .routes(routes!(max_supply))
+        .routes(routes!(mintable_supply))
+        .routes(routes!(circulating_supply))
+        .routes(routes!(circulating_supply_breakdown))
+        .routes(routes!(staked_supply))
+        .routes(routes!(allocations))
+        .routes(routes!(coingecko_supply))
+        .routes(routes!(coingecko_supply_figure))
+        .routes(routes!(cmc_supply))
+        .routes(routes!(cmc_supply_figure))
+        .routes(routes!(staking_pools))
+        .routes(routes!(staking_summary))
+        .routes(routes!(pool_events))
+        .routes(routes!(staker_events))
+        .routes(routes!(vesting_violations))
+}
+
+/// Scans for new pools in the background and swaps the merged list into the shared state after
+/// every scan, so requests are served from the manual and persisted pools while a scan runs.
+async fn run_pool_discovery(
+    client: Arc<Provider<Http>>,
+    config: DiscoveryConfig,
+    mut discovered: DiscoveredPools,
+    manual: Vec<(Address, bool)>,
+    excluded: Vec<ExcludedAddress>,
+    pools: Arc<RwLock<Vec<(Address, bool)>>>,
+    interval: Duration,
+) {
+    loop {
+        match discovery::discover(client.as_ref(), &config, &mut discovered).await {
+            Ok(found) => eprintln!("Pool discovery scanned up to block {:?}, {} new pool(s)", discovered.last_scanned_block, found),
+            Err(e) => eprintln!("Pool discovery failed: {:?}", e),
+        }
+        // Pools found before a failed window are still merged
+        *pools.write().expect("pool address lock poisoned") = discovery::merge_pools(&manual, &discovered, &excluded);
+
+        if interval.is_zero() {
+            return;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Follows staking events for the current pool list, including pools added by discovery, which
+/// the indexer backfills from `from_block` the first time it sees them.
+async fn run_staking_indexer(client: Arc<Provider<Http>>, config: IndexerConfig, store: Arc<RwLock<EventStore>>, pools: Arc<RwLock<Vec<(Address, bool)>>>, interval: Duration) {
+    loop {
+        let addresses: Vec<Address> = pools.read().expect("pool address lock poisoned").iter().map(|&(address, _)| address).collect();
+        match indexer::index(client.as_ref(), &config, &store, &addresses).await {
+            Ok(stored) if stored > 0 => eprintln!("Staking indexer stored {} event(s)", stored),
+            Ok(_) => {}
+            Err(e) => eprintln!("Staking indexer failed: {:?}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Checks vesting wallets for early outflows, publishing the state after every check and
+/// sending each new violation to `webhooks`.
+#[allow(clippy::too_many_arguments)]
+async fn run_outflow_monitor(
+    token: Arc<ERC20<Provider<Http>>>,
+    schedules: Vec<PoolSchedule>,
+    tge_timestamp: U256,
+    multicall: MulticallConfig,
+    config: MonitorConfig,
+    shared: Arc<RwLock<MonitorState>>,
+    webhooks: Dispatcher,
+    interval: Duration,
+    decimals: u8,
+) {
+    loop {
+        // This task is the only writer, so it checks a copy and swaps it in when done
+        let mut state = shared.read().expect("monitor state lock poisoned").clone();
+        match monitor::check(token.as_ref(), &schedules, tge_timestamp, &multicall, &config, &mut state, decimals).await {
+            Ok(found) => {
+                *shared.write().expect("monitor state lock poisoned") = state;
+                for violation in found {
+                    webhooks.dispatch(&Alert::VestingViolation { violation }).await;
+                }
+            }
+            Err(e) => eprintln!("Outflow monitor failed: {:?}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Recomputes circulating supply on a timer and sends the alerts raised by each refresh to
+/// `webhooks`.
+async fn run_supply_refresh(state: Arc<AppState>, mut watcher: SupplyWatcher, webhooks: Dispatcher, interval: Duration) {
+    loop {
+        let alerts = match compute_circulating_supply(&state).await {
+            Ok(breakdown) => watcher.observe(&breakdown),
+            Err(e) => {
+                eprintln!("Supply refresh failed: {:?}", e);
+                watcher.failed(&e)
+            }
+        };
+        for alert in &alerts {
+            webhooks.dispatch(alert).await;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Renders a supply figure as plain text, or as `{"<name>": "<value>"}` when the client accepts
+/// JSON.
+fn supply_response(name: &str, value: U256, format: &AmountFormat, headers: &HeaderMap, decimals: u8) -> Response {
+    let formatted = format.format(value, decimals);
+    let wants_json = headers.get(header::ACCEPT).and_then(|accept| accept.to_str().ok()).is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        Json(serde_json::json!({ name: formatted })).into_response()
+    } else {
+        formatted.into_response()
+    }
+}
+
+#[utoipa::path(get, path = "/total-supply", tag = "supply", params(AmountFormat), responses(
+    (status = 200, description = "Total supply across Matchain and BSC net of burns; `0` if it could not be read", content((String = "text/plain"), (Object = "application/json"))),
+))]
+async fn total_supply(State(state): State<Arc<AppState>>, Query(format): Query<AmountFormat>, headers: HeaderMap) -> Response {
+    let value = match supply::get_total_supply_wei(&state.matchain_contract, &state.bsc_contract, &state.matchain_multicall, &state.bsc_multicall).await {
         Ok(value) => value,
         Err(e) => {
             eprintln!("Error calculating total supply: {:?}", e);
-            "0".to_string()
+            U256::zero()
+        }
+    };
+    supply_response("total_supply", value, &format, &headers, state.decimals)
+}
+
+#[utoipa::path(get, path = "/max-supply", tag = "supply", params(AmountFormat), responses(
+    (status = 200, description = "Max supply", content((String = "text/plain"), (Object = "application/json"))),
+    (status = 404, description = "`MAX_SUPPLY` is not set", body = String),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn max_supply(State(state): State<Arc<AppState>>, Query(format): Query<AmountFormat>, headers: HeaderMap) -> Result<Response, (StatusCode, String)> {
+    match supply::get_max_supply(&state.matchain_contract, state.max_supply).await {
+        Ok(Some(value)) => Ok(supply_response("max_supply", value, &format, &headers, state.decimals)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Max supply is not configured".to_string())),
+        Err(e) => {
+            eprintln!("Error reading max supply: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+/// What can still be minted: a fixed max supply minus the total supply of both chains, or
+/// Matchain's `cap()` minus its `totalSupply()`.
+#[utoipa::path(get, path = "/mintable-supply", tag = "supply", params(AmountFormat), responses(
+    (status = 200, description = "Max supply minus minted supply", content((String = "text/plain"), (Object = "application/json"))),
+    (status = 404, description = "`MAX_SUPPLY` is not set", body = String),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn mintable_supply(State(state): State<Arc<AppState>>, Query(format): Query<AmountFormat>, headers: HeaderMap) -> Result<Response, (StatusCode, String)> {
+    match supply::get_mintable_supply_wei(&state.matchain_contract, &state.bsc_contract, &state.matchain_multicall, &state.bsc_multicall, state.max_supply).await {
+        Ok(Some(mintable)) => Ok(supply_response("mintable_supply", mintable, &format, &headers, state.decimals)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Max supply is not configured".to_string())),
+        Err(e) => {
+            eprintln!("Error calculating mintable supply: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/circulating-supply", tag = "supply", params(AmountFormat), responses(
+    (status = 200, description = "Circulating supply; `0` if it could not be calculated", content((String = "text/plain"), (Object = "application/json"))),
+))]
+async fn circulating_supply(State(state): State<Arc<AppState>>, Query(format): Query<AmountFormat>, headers: HeaderMap) -> Response {
+    let value = match compute_circulating_supply(&state).await {
+        Ok(breakdown) => breakdown.circulating_supply_wei,
+        Err(e) => {
+            eprintln!("Error calculating circulating supply: {:?}", e);
+            U256::zero()
+        }
+    };
+    supply_response("circulating_supply", value, &format, &headers, state.decimals)
+}
+
+#[utoipa::path(get, path = "/circulating-supply/breakdown", tag = "supply", responses(
+    (status = 200, description = "Circulating supply with per-schedule and per-pool details", body = SupplyBreakdown),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn circulating_supply_breakdown(State(state): State<Arc<AppState>>) -> Result<Json<SupplyBreakdown>, (StatusCode, String)> {
+    let mut breakdown = match compute_circulating_supply(&state).await {
+        Ok(breakdown) => breakdown,
+        Err(e) => {
+            eprintln!("Error calculating circulating supply breakdown: {:?}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+        }
+    };
+    // Staked supply is informational here, so failing to read it leaves `staked` empty
+    match supply::get_staked_supply(state.matchain_contract.client(), &breakdown, &state.pool_addresses(), &state.matchain_multicall, state.decimals).await {
+        Ok((staked, failures)) => {
+            breakdown.staked = Some(staked);
+            breakdown.staking_failures = failures;
+        }
+        Err(e) => eprintln!("Error calculating staked supply for the breakdown: {:?}", e),
+    }
+    Ok(Json(breakdown))
+}
+
+#[utoipa::path(get, path = "/staked-supply", tag = "staking", responses(
+    (status = 200, description = "MAT staked across all pools", body = StakedSupplyReport),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn staked_supply(State(state): State<Arc<AppState>>) -> Result<Json<StakedSupplyReport>, (StatusCode, String)> {
+    match compute_staked_supply(&state).await {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => {
+            eprintln!("Error calculating staked supply: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+/// Vesting allocations as a tokenomics table, with shares of max supply (or of total supply when
+/// no max supply is configured) and per-category totals.
+#[utoipa::path(get, path = "/allocations", tag = "supply", responses(
+    (status = 200, description = "Allocations and category totals", body = AllocationsReport),
+    (status = 500, description = "The figures could not be calculated", body = String),
+))]
+async fn allocations(State(state): State<Arc<AppState>>) -> Result<Json<AllocationsReport>, (StatusCode, String)> {
+    let result = tokio::try_join!(
+        compute_circulating_supply(&state),
+        supply::get_max_supply(&state.matchain_contract, state.max_supply),
+        supply::get_total_supply_wei(&state.matchain_contract, &state.bsc_contract, &state.matchain_multicall, &state.bsc_multicall),
+    );
+    match result {
+        Ok((breakdown, max, total)) => {
+            let (basis, basis_supply) = match max {
+                Some(max) => (SupplyBasis::Max, max),
+                None => (SupplyBasis::Total, total),
+            };
+            Ok(Json(allocations::allocations_report(&breakdown, basis, basis_supply, state.decimals)))
+        }
+        Err(e) => {
+            eprintln!("Error calculating allocations: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
         }
     }
 }
 
-async fn circulating_supply(State(state): State<Arc<AppState>>) -> String {
-    match supply::get_circulating_supply(
+async fn compute_circulating_supply(state: &AppState) -> Result<SupplyBreakdown, anyhow::Error> {
+    supply::get_circulating_supply(
         &state.matchain_contract,
         &state.excluded_addresses,
         &state.pool_data,
-        &state.onchain_pool_addresses,
-        state.tge_timestamp,
+        &state.pool_addresses(),
+        &state.known_stakers(),
+        &state.matchain_multicall,
+        &state.supply_options,
         state.decimals,
     )
     .await
-    {
-        Ok(value) => value,
+}
+
+async fn compute_staked_supply(state: &AppState) -> Result<StakedSupplyReport, anyhow::Error> {
+    let breakdown = compute_circulating_supply(state).await?;
+    let (staked, failures) = supply::get_staked_supply(state.matchain_contract.client(), &breakdown, &state.pool_addresses(), &state.matchain_multicall, state.decimals).await?;
+    Ok(StakedSupplyReport::new(breakdown, staked, failures))
+}
+
+/// Reads total and circulating supply concurrently for the aggregator routes.
+async fn compute_supply_figures(state: &AppState) -> Result<SupplyFigures, anyhow::Error> {
+    let (total, breakdown, max) = tokio::try_join!(
+        supply::get_total_supply_wei(&state.matchain_contract, &state.bsc_contract, &state.matchain_multicall, &state.bsc_multicall),
+        compute_circulating_supply(state),
+        supply::get_max_supply(&state.matchain_contract, state.max_supply),
+    )?;
+    Ok(SupplyFigures { total, circulating: breakdown.circulating_supply_wei, max })
+}
+
+async fn supply_figure(state: &AppState, figure: SupplyFigure) -> Result<U256, (StatusCode, String)> {
+    let figures = compute_supply_figures(state).await.map_err(|e| {
+        eprintln!("Error calculating supply figures: {:?}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+    figures.get(figure).ok_or((StatusCode::NOT_FOUND, "Max supply is not configured".to_string()))
+}
+
+#[utoipa::path(get, path = "/coingecko/supply", tag = "aggregators", responses(
+    (status = 200, description = "All supply figures at full precision", body = CoinGeckoResult<SupplyValues>),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn coingecko_supply(State(state): State<Arc<AppState>>) -> Result<Json<CoinGeckoResult<SupplyValues>>, (StatusCode, String)> {
+    match compute_supply_figures(&state).await {
+        Ok(figures) => Ok(Json(aggregators::coingecko_supply(&figures, state.decimals))),
         Err(e) => {
-            eprintln!("Error calculating circulating supply: {:?}", e);
-            "0".to_string()
+            eprintln!("Error calculating CoinGecko supply: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/coingecko/supply/{figure}", tag = "aggregators", params(("figure" = SupplyFigure, Path)), responses(
+    (status = 200, description = "One supply figure at full precision", body = CoinGeckoResult<String>),
+    (status = 404, description = "`MAX_SUPPLY` is not set", body = String),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn coingecko_supply_figure(State(state): State<Arc<AppState>>, Path(figure): Path<SupplyFigure>) -> Result<Json<CoinGeckoResult<String>>, (StatusCode, String)> {
+    let value = supply_figure(&state, figure).await?;
+    Ok(Json(aggregators::coingecko_figure(value, state.decimals)))
+}
+
+#[utoipa::path(get, path = "/cmc/supply", tag = "aggregators", responses(
+    (status = 200, description = "All supply figures truncated to 8 decimals", body = SupplyValues),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn cmc_supply(State(state): State<Arc<AppState>>) -> Result<Json<SupplyValues>, (StatusCode, String)> {
+    match compute_supply_figures(&state).await {
+        Ok(figures) => Ok(Json(aggregators::cmc_supply(&figures, state.decimals))),
+        Err(e) => {
+            eprintln!("Error calculating CoinMarketCap supply: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/cmc/supply/{figure}", tag = "aggregators", params(("figure" = SupplyFigure, Path)), responses(
+    (status = 200, description = "One supply figure truncated to 8 decimals", body = String, content_type = "text/plain"),
+    (status = 404, description = "`MAX_SUPPLY` is not set", body = String),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn cmc_supply_figure(State(state): State<Arc<AppState>>, Path(figure): Path<SupplyFigure>) -> Result<String, (StatusCode, String)> {
+    let value = supply_figure(&state, figure).await?;
+    Ok(aggregators::cmc_figure(value, state.decimals))
+}
+
+#[utoipa::path(get, path = "/staking/pools", tag = "staking", responses(
+    (status = 200, description = "Staking state of every pool", body = Vec<PoolStakingInfo>),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn staking_pools(State(state): State<Arc<AppState>>) -> Result<Json<Vec<PoolStakingInfo>>, (StatusCode, String)> {
+    match staking::get_staking_pools(state.matchain_contract.client(), &state.pool_addresses(), &state.known_stakers(), &state.matchain_multicall, state.decimals).await {
+        Ok(pools) => Ok(Json(pools)),
+        Err(e) => {
+            eprintln!("Error reading staking pools: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/staking/summary", tag = "staking", responses(
+    (status = 200, description = "Staking state aggregated across pools", body = StakingSummary),
+    (status = 500, description = "The figure could not be calculated", body = String),
+))]
+async fn staking_summary(State(state): State<Arc<AppState>>) -> Result<Json<StakingSummary>, (StatusCode, String)> {
+    match staking::get_staking_summary(state.matchain_contract.client(), &state.pool_addresses(), &state.known_stakers(), &state.matchain_multicall, state.decimals).await {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => {
+            eprintln!("Error calculating staking summary: {:?}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
         }
     }
-}
\ No newline at end of file
+}
+
+#[utoipa::path(get, path = "/staking/pools/{address}/events", tag = "staking", params(("address" = String, Path, description = "Pool address"), HistoryQuery), responses(
+    (status = 200, description = "Indexed events of the pool, oldest first", body = EventHistory),
+    (status = 404, description = "Not a known staking pool", body = String),
+    (status = 503, description = "The staking event indexer is disabled", body = String),
+))]
+async fn pool_events(State(state): State<Arc<AppState>>, Path(pool): Path<Address>, Query(query): Query<HistoryQuery>) -> Result<Json<EventHistory>, (StatusCode, String)> {
+    let store = state.staking_events.as_ref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Staking event indexer is disabled".to_string()))?;
+    let store = store.read().expect("event store lock poisoned");
+    if store.indexed_to(pool).is_none() && !state.pool_addresses().iter().any(|&(address, _)| address == pool) {
+        return Err((StatusCode::NOT_FOUND, format!("{:?} is not a known staking pool", pool)));
+    }
+    Ok(Json(store.pool_history(pool, query.from_block, query.to_block, state.decimals)))
+}
+
+#[utoipa::path(get, path = "/monitoring/violations", tag = "monitoring", responses(
+    (status = 200, description = "Vesting wallets found holding less than their locked amount, with the transfers out that caused it", body = ViolationReport),
+    (status = 503, description = "The outflow monitor is disabled", body = String),
+))]
+async fn vesting_violations(State(state): State<Arc<AppState>>) -> Result<Json<ViolationReport>, (StatusCode, String)> {
+    let monitor_state = state.violations.as_ref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Outflow monitor is disabled".to_string()))?;
+    Ok(Json(monitor_state.read().expect("monitor state lock poisoned").report()))
+}
+
+#[utoipa::path(get, path = "/staking/stakers/{address}/events", tag = "staking", params(("address" = String, Path, description = "Staker address"), HistoryQuery), responses(
+    (status = 200, description = "Indexed events involving the staker across all pools, oldest first", body = EventHistory),
+    (status = 503, description = "The staking event indexer is disabled", body = String),
+))]
+async fn staker_events(State(state): State<Arc<AppState>>, Path(staker): Path<Address>, Query(query): Query<HistoryQuery>) -> Result<Json<EventHistory>, (StatusCode, String)> {
+    let store = state.staking_events.as_ref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Staking event indexer is disabled".to_string()))?;
+    let store = store.read().expect("event store lock poisoned");
+    Ok(Json(store.staker_history(staker, query.from_block, query.to_block, state.decimals)))
+}
@@ -0,0 +1,194 @@
+// src/monitor.rs
+//! Watches vesting wallets for tokens moved out before they unlocked. Every check compares each
+//! schedule's wallet balance with its locked amount and attributes shortfalls to the `Transfer`
+//! logs out of its wallets since the previous check. Balances and logs are both read up to the
+//! confirmed block, so a shortfall is only seen together with the transfers that caused it.
+use crate::ERC20;
+use crate::config::{AllocationLabel, PoolSchedule, matchain_schedule_wallets};
+use crate::multicall::MulticallConfig;
+use crate::supply::{self, calculate_schedule_vesting};
+use crate::utils;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, H256, Log, U256};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::path::{Path, PathBuf};
+
+pub const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    /// First block whose transfers are attributed; `None` starts at the head on the first check.
+    pub from_block: Option<u64>,
+    pub block_range: u64,
+    /// How many blocks each check stays behind the head, so balances and logs from reorged blocks
+    /// are not used. Transfers in those blocks are attributed by a later check.
+    pub confirmations: u64,
+    pub state_path: PathBuf,
+}
+
+/// A transfer out of a vesting wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Outflow {
+    #[schema(value_type = String)]
+    pub from: Address,
+    #[schema(value_type = String)]
+    pub to: Address,
+    pub amount: String,
+    pub block: u64,
+    #[schema(value_type = String)]
+    pub transaction_hash: H256,
+    pub log_index: u64,
+}
+
+/// A schedule whose wallets held less than its locked amount.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Violation {
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Address>,
+    #[serde(flatten)]
+    pub label: AllocationLabel,
+    /// Confirmed block the check read balances at, and its timestamp.
+    pub block: u64,
+    pub timestamp: u64,
+    pub locked: String,
+    pub balance: String,
+    pub shortfall: String,
+    /// Transfers out of the schedule's wallets since the previous check. Empty when the shortfall
+    /// predates the first check.
+    pub outflows: Vec<Outflow>,
+}
+
+/// Recorded violations and how far transfers have been checked, persisted between runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MonitorState {
+    pub last_checked_block: Option<u64>,
+    /// First wallet of every schedule that was below its locked amount at the last check, so an
+    /// ongoing shortfall is only recorded again when more tokens leave.
+    pub open: Vec<Address>,
+    pub violations: Vec<Violation>,
+}
+
+/// Recorded violations, oldest first.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ViolationReport {
+    /// Last block whose transfers have been checked; `null` before the first check.
+    pub last_checked_block: Option<u64>,
+    pub violations: Vec<Violation>,
+}
+
+impl MonitorState {
+    pub fn report(&self) -> ViolationReport {
+        ViolationReport { last_checked_block: self.last_checked_block, violations: self.violations.clone() }
+    }
+
+    /// Loads the state file, starting empty if it does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        utils::load_json_state(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        utils::save_json_state(self, path)
+    }
+}
+
+/// Checks every schedule with Matchain wallets at the confirmed head, appending new violations to
+/// `state` and saving it. Returns the violations found by this check.
+pub async fn check<M: Middleware + Clone + 'static>(
+    token: &ERC20<M>,
+    schedules: &[PoolSchedule],
+    tge_timestamp: U256,
+    multicall: &MulticallConfig,
+    config: &MonitorConfig,
+    state: &mut MonitorState,
+    decimals: u8,
+) -> Result<Vec<Violation>, anyhow::Error> {
+    let client = token.client();
+    let head = client.get_block_number().await?.as_u64();
+    let confirmed = head.saturating_sub(config.confirmations);
+    // A zero timestamp would treat every schedule as fully locked and flag every wallet that has
+    // spent unlocked tokens
+    let timestamp = client.get_block(confirmed).await?.ok_or_else(|| anyhow::anyhow!("block {} not found", confirmed))?.timestamp;
+
+    let wallets = matchain_schedule_wallets(schedules);
+    if wallets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let from = state.last_checked_block.map_or(config.from_block.unwrap_or(confirmed), |block| block + 1);
+    let outflows = if from <= confirmed { outflows(client.as_ref(), token.address(), &wallets, from, confirmed, config.block_range, decimals).await? } else { Vec::new() };
+
+    // Balances at the head could already reflect transfers whose logs are not confirmed yet,
+    // which would record the shortfall now and again once its transfer is attributed
+    let balances = multicall.call_u256(client.clone(), wallets.iter().map(|&wallet| token.balance_of(wallet).block(confirmed)).collect()).await?;
+    let balances: Vec<(Address, U256)> = wallets.iter().copied().zip(balances).collect();
+
+    let mut found = Vec::new();
+    let mut open = Vec::new();
+    for schedule in schedules {
+        let Some(balance) = supply::schedule_balance(schedule, &balances) else {
+            continue;
+        };
+        let Some(&(key, _)) = schedule.addresses.iter().find(|(_, chain)| chain == "Matchain") else {
+            continue;
+        };
+        let locked = calculate_schedule_vesting(schedule, U256::from(1_000_000u64), timestamp, tge_timestamp).locked_amount;
+        if balance >= locked {
+            continue;
+        }
+        open.push(key);
+
+        // Moving tokens between the schedule's own wallets leaves its balance unchanged
+        let own = |address: Address| schedule.addresses.iter().any(|(addr, _)| *addr == address);
+        let schedule_outflows: Vec<Outflow> = outflows.iter().filter(|outflow| own(outflow.from) && !own(outflow.to)).cloned().collect();
+        if state.open.contains(&key) && schedule_outflows.is_empty() {
+            continue;
+        }
+        let violation = Violation {
+            addresses: schedule.addresses.iter().map(|(addr, _)| *addr).collect(),
+            label: schedule.label.clone(),
+            block: confirmed,
+            timestamp: utils::saturating_u64(timestamp),
+            locked: utils::u256_to_human(locked, decimals),
+            balance: utils::u256_to_human(balance, decimals),
+            shortfall: utils::u256_to_human(locked - balance, decimals),
+            outflows: schedule_outflows,
+        };
+        eprintln!("Vesting wallet violation for {:?}: {} locked, {} held", violation.addresses, violation.locked, violation.balance);
+        found.push(violation);
+    }
+
+    state.open = open;
+    state.last_checked_block = Some(state.last_checked_block.map_or(confirmed, |block| block.max(confirmed)));
+    state.violations.extend(found.iter().cloned());
+    state.save(&config.state_path)?;
+    Ok(found)
+}
+
+/// Transfers out of `wallets` in `from..=to`, read in windows of `block_range`. Transfers from a
+/// wallet to itself are ignored.
+async fn outflows<M: Middleware + 'static>(client: &M, token: Address, wallets: &[Address], from: u64, to: u64, block_range: u64, decimals: u8) -> Result<Vec<Outflow>, anyhow::Error> {
+    let senders: Vec<H256> = wallets.iter().map(|&wallet| H256::from(wallet)).collect();
+    let mut outflows = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = to.min(start.saturating_add(block_range.max(1) - 1));
+        let filter = Filter::new().address(token).event(TRANSFER_EVENT).topic1(senders.clone()).from_block(start).to_block(end);
+        outflows.extend(client.get_logs(&filter).await?.iter().filter_map(|log| outflow(log, decimals)).filter(|outflow| outflow.from != outflow.to));
+        start = end + 1;
+    }
+    Ok(outflows)
+}
+
+/// Decodes `Transfer(address indexed from, address indexed to, uint256 value)`.
+fn outflow(log: &Log, decimals: u8) -> Option<Outflow> {
+    let (from, to) = (log.topics.get(1)?, log.topics.get(2)?);
+    Some(Outflow {
+        from: Address::from_slice(&from.as_bytes()[12..]),
+        to: Address::from_slice(&to.as_bytes()[12..]),
+        amount: utils::u256_to_human(U256::from_big_endian(log.data.get(..32)?), decimals),
+        block: log.block_number?.as_u64(),
+        transaction_hash: log.transaction_hash?,
+        log_index: log.log_index?.as_u64(),
+    })
+}
@@ -0,0 +1,209 @@
+// src/multicall.rs
+use ethers::contract::multicall_contract::{Call3, Multicall3};
+use ethers::contract::{ContractCall, MULTICALL_ADDRESS};
+use ethers::abi::{Detokenize, Token};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use futures::future::try_join_all;
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::fmt;
+use std::sync::Arc;
+
+// Keeps a single aggregate3 call well below typical RPC gas and response-size caps
+pub const DEFAULT_MAX_CALLS_PER_BATCH: usize = 100;
+
+/// A call paired with whether it may fail without failing its batch.
+pub type FallibleCall<M, D> = (ContractCall<M, D>, bool);
+
+#[derive(Debug, Clone, Copy)]
+pub struct MulticallConfig {
+    /// Multicall3 deployment to aggregate through; `None` falls back to one `eth_call` per call.
+    pub address: Option<Address>,
+    pub max_calls_per_batch: usize,
+}
+
+impl Default for MulticallConfig {
+    fn default() -> Self {
+        Self {
+            address: Some(MULTICALL_ADDRESS),
+            max_calls_per_batch: DEFAULT_MAX_CALLS_PER_BATCH,
+        }
+    }
+}
+
+impl MulticallConfig {
+    pub fn with_address(mut self, address: Option<Address>) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn with_max_calls_per_batch(mut self, max_calls_per_batch: usize) -> Self {
+        // A zero batch size would never make progress
+        self.max_calls_per_batch = max_calls_per_batch.max(1);
+        self
+    }
+
+    /// Checks that contract code exists at the configured address and drops to direct calls if
+    /// not, so chains without a Multicall3 deployment keep working.
+    pub async fn detect<M: Middleware + 'static>(self, client: &M, chain: &str) -> Result<Self, anyhow::Error> {
+        let Some(address) = self.address else {
+            eprintln!("Multicall disabled for {}; using direct eth_call", chain);
+            return Ok(self);
+        };
+
+        let code = client.get_code(address, None).await.map_err(|e| anyhow::anyhow!("Failed to probe multicall contract on {}: {}", chain, e))?;
+        if code.is_empty() {
+            eprintln!("No multicall contract at {:?} on {}; falling back to direct eth_call", address, chain);
+            Ok(self.with_address(None))
+        } else {
+            eprintln!("Using multicall contract {:?} on {}", address, chain);
+            Ok(self)
+        }
+    }
+
+    /// Executes `calls` in batches of at most `max_calls_per_batch`, running the batches
+    /// concurrently and returning the results in the same order as `calls`. Calls pinned to a
+    /// block with `.block(..)` are read at that block; a batch should not mix blocks.
+    pub async fn call_u256<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        calls: Vec<ContractCall<M, U256>>,
+    ) -> Result<Vec<U256>, anyhow::Error> {
+        let results = self.try_call(client, calls.into_iter().map(|call| (call, false)).collect()).await?;
+        results
+            .into_iter()
+            .map(|result| {
+                let token = result.map_err(|failure| anyhow::anyhow!("{}", failure))?;
+                token.into_uint().ok_or_else(|| anyhow::anyhow!("Multicall returned a non-uint256 value"))
+            })
+            .collect()
+    }
+
+    /// Like [`Self::call_u256`], but returns raw tokens and lets calls flagged with
+    /// `allow_failure` report a [`CallFailure`] in their slot instead of failing the whole batch.
+    /// A failing call that is not allowed to fail still returns an error.
+    pub async fn try_call<M: Middleware + 'static, D: Detokenize>(
+        &self,
+        client: Arc<M>,
+        calls: Vec<FallibleCall<M, D>>,
+    ) -> Result<Vec<Result<Token, CallFailure>>, anyhow::Error> {
+        let mut batches: Vec<Vec<FallibleCall<M, D>>> = Vec::new();
+        for call in calls {
+            match batches.last_mut() {
+                Some(batch) if batch.len() < self.max_calls_per_batch => batch.push(call),
+                _ => batches.push(vec![call]),
+            }
+        }
+        let batch_count = batches.len();
+
+        let pending = batches.into_iter().map(|batch| {
+            let client = client.clone();
+            async move {
+                let labels: Vec<(Address, String)> = batch.iter().map(|(call, _)| CallFailure::label(call)).collect();
+                match self.address {
+                    Some(address) => {
+                        // aggregate3 is called directly so each result is decoded here: ethers'
+                        // Multicall fails the whole batch when one successful result has the wrong shape
+                        let calls = batch
+                            .iter()
+                            .zip(&labels)
+                            .map(|((call, allow_failure), (target, _))| Call3 { target: *target, allow_failure: *allow_failure, call_data: call.calldata().unwrap_or_default() })
+                            .collect();
+                        let mut aggregate = Multicall3::new(address, client).aggregate_3(calls);
+                        // The aggregate call is read at the block its calls were pinned to, if any
+                        aggregate.block = batch.first().and_then(|(call, _)| call.block);
+                        let results = aggregate.call().await?;
+                        // A short response would otherwise leave the missing calls out silently
+                        if results.len() != labels.len() {
+                            return Err(anyhow::anyhow!("Multicall returned {} results for {} calls", results.len(), labels.len()));
+                        }
+                        let mut decoded = Vec::with_capacity(batch.len());
+                        for (((call, allow_failure), (target, function)), result) in batch.into_iter().zip(labels).zip(results) {
+                            let result = if result.success {
+                                decode_output(&call, &result.return_data)
+                            } else if result.return_data.is_empty() {
+                                Err("reverted without data".to_string())
+                            } else {
+                                Err(format!("reverted: {}", result.return_data))
+                            };
+                            match result {
+                                Ok(token) => decoded.push(Ok(token)),
+                                Err(reason) if allow_failure => decoded.push(Err(CallFailure { target, function, reason })),
+                                Err(reason) => return Err(anyhow::anyhow!("{}() on {:?} failed: {}", function, target, reason)),
+                            }
+                        }
+                        Ok::<_, anyhow::Error>(decoded)
+                    }
+                    None => {
+                        // Without an aggregator each call in the batch goes out as its own eth_call
+                        let mut results = Vec::with_capacity(batch.len());
+                        for ((call, allow_failure), (target, function)) in batch.into_iter().zip(labels) {
+                            let result = match call.call_raw_bytes().await {
+                                Ok(data) => decode_output(&call, &data),
+                                Err(e) => Err(e.to_string()),
+                            };
+                            match result {
+                                Ok(token) => results.push(Ok(token)),
+                                Err(reason) if allow_failure => results.push(Err(CallFailure { target, function, reason })),
+                                Err(reason) => return Err(anyhow::anyhow!("{}() on {:?} failed: {}", function, target, reason)),
+                            }
+                        }
+                        Ok(results)
+                    }
+                }
+            }
+        });
+
+        // try_join_all preserves input order, so flattening restores the original call order
+        let results: Vec<Result<Token, CallFailure>> = try_join_all(pending).await?.into_iter().flatten().collect();
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        eprintln!(
+            "{}: {} calls in {} batch(es) of up to {}, {} failed",
+            if self.address.is_some() { "Multicall" } else { "Direct eth_call" },
+            results.len(),
+            batch_count,
+            self.max_calls_per_batch,
+            failed
+        );
+
+        Ok(results)
+    }
+}
+
+/// Decodes a call's return data into a single token, or a tuple for multiple outputs.
+fn decode_output<M, D>(call: &ContractCall<M, D>, data: &[u8]) -> Result<Token, String> {
+    let mut tokens = call.function.decode_output(data).map_err(|e| format!("undecodable return value: {}", e))?;
+    Ok(if tokens.len() == 1 { tokens.remove(0) } else { Token::Tuple(tokens) })
+}
+
+/// An individual call that was allowed to fail and did.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CallFailure {
+    #[schema(value_type = String)]
+    pub target: Address,
+    pub function: String,
+    pub reason: String,
+}
+
+impl CallFailure {
+    fn label<M, D>(call: &ContractCall<M, D>) -> (Address, String) {
+        (call.tx.to_addr().copied().unwrap_or_default(), call.function.name.clone())
+    }
+}
+
+impl fmt::Display for CallFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}() on {:?} failed: {}", self.function, self.target, self.reason)
+    }
+}
+
+/// Parses a multicall address setting; empty or `none` disables multicall for the chain.
+pub fn parse_address_setting(value: Option<String>) -> Result<Option<Address>, anyhow::Error> {
+    match value.as_deref().map(str::trim) {
+        None => Ok(Some(MULTICALL_ADDRESS)),
+        Some("") => Ok(None),
+        Some(value) if value.eq_ignore_ascii_case("none") => Ok(None),
+        Some(value) => Ok(Some(value.parse::<Address>()?)),
+    }
+}
@@ -0,0 +1,39 @@
+// src/openapi.rs
+use crate::aggregators::SupplyFigure;
+use crate::utils::{Rounding, Unit};
+use utoipa::OpenApi;
+
+/// Top-level OpenAPI metadata; paths and schemas are collected from the router's handlers.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Matchain Supply APIs", description = "Total, circulating, max and staked supply of MAT.", license(name = "MIT")),
+    // Only referenced from query and path parameters, which do not register their schemas
+    components(schemas(Unit, Rounding, SupplyFigure)),
+    tags(
+        (name = "supply", description = "Supply figures"),
+        (name = "aggregators", description = "Supply in CoinGecko and CoinMarketCap formats"),
+        (name = "staking", description = "StakingPool state and event history"),
+        (name = "monitoring", description = "Vesting wallet outflow violations"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Swagger UI page for `/docs`, rendering `/openapi.json`. The UI assets load from unpkg.
+pub const DOCS_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Matchain Supply APIs</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
@@ -0,0 +1,343 @@
+// src/staking.rs
+use crate::multicall::{CallFailure, FallibleCall, MulticallConfig};
+use crate::{StakingPool, utils};
+use ethers::abi::{AbiError, Token};
+use ethers::contract::ContractCall;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A single StakingPool value read for staking analytics; it both builds the call and applies
+/// the decoded result to a [`PoolStakingReading`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StakingRead {
+    Owner,
+    Active,
+    Fee,
+    Ratio,
+    Stakers,
+    TotalStake,
+    UnbondingPeriod,
+    CurrentStake(Address),
+    PendingUnstakes(Address),
+}
+
+impl StakingRead {
+    /// ABI method name, which is also how failed reads are labelled.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Active => "active",
+            Self::Fee => "fee",
+            Self::Ratio => "ratio",
+            Self::Stakers => "stakers",
+            Self::TotalStake => "totalStake",
+            Self::UnbondingPeriod => "UNBONDING_PERIOD",
+            Self::CurrentStake(_) => "currentStake",
+            Self::PendingUnstakes(_) => "pendingUnstakes",
+        }
+    }
+
+    fn call<M: Middleware + 'static>(self, pool: &StakingPool<M>) -> Result<ContractCall<M, Token>, AbiError> {
+        match self {
+            Self::CurrentStake(staker) | Self::PendingUnstakes(staker) => pool.method(self.name(), staker),
+            _ => pool.method(self.name(), ()),
+        }
+    }
+
+    fn apply(self, reading: &mut PoolStakingReading, token: Token) -> Option<()> {
+        match self {
+            Self::Owner => reading.owner = Some(token.into_address()?),
+            Self::Active => reading.active = token.into_bool()?,
+            Self::Fee => reading.fee = token.into_uint()?,
+            Self::Ratio => reading.ratio = token.into_uint()?,
+            Self::Stakers => reading.stakers = token.into_uint()?,
+            Self::TotalStake => reading.total_stake = token.into_uint()?,
+            Self::UnbondingPeriod => reading.unbonding_period = token.into_uint()?,
+            Self::CurrentStake(_) => reading.self_stake = token.into_uint()?,
+            Self::PendingUnstakes(staker) => {
+                // Each entry is an Unstake { amount, block } struct
+                for unstake in token.into_array()? {
+                    let mut fields = unstake.into_tuple()?.into_iter();
+                    let amount = fields.next()?.into_uint()?;
+                    let block = fields.next()?.into_uint()?;
+                    if reading.owner == Some(staker) {
+                        reading.owner_pending_unstakes = reading.owner_pending_unstakes.checked_add(amount)?;
+                    }
+                    if let Some(pending) = reading.pending_unstakes.as_mut() {
+                        *pending = pending.checked_add(amount)?;
+                    }
+                    reading.unstakes.push((amount, block));
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+/// Raw on-chain staking state of one pool. Values whose call failed are left at their default
+/// and listed in `failures`.
+#[derive(Debug, Clone, Default)]
+pub struct PoolStakingReading {
+    pub address: Address,
+    pub reserved: bool,
+    pub owner: Option<Address>,
+    pub active: bool,
+    pub fee: U256,
+    pub ratio: U256,
+    pub stakers: U256,
+    pub total_stake: U256,
+    /// The owner's own stake in the pool.
+    pub self_stake: U256,
+    /// Tokens the owner has unstaked and not yet claimed.
+    pub owner_pending_unstakes: U256,
+    /// Tokens unstaked and not yet claimed by the owner and every known staker; `None` when the
+    /// pool's stakers are not known.
+    pub pending_unstakes: Option<U256>,
+    /// Every pending unstake read as `(amount, block)` pairs.
+    pub unstakes: Vec<(U256, U256)>,
+    /// `UNBONDING_PERIOD` in blocks.
+    pub unbonding_period: U256,
+    /// Pending unstakes still inside the unbonding period at the block they were read at; `None`
+    /// when the pool's stakers are not known.
+    pub unbonding: Option<U256>,
+    pub failures: Vec<CallFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStakingInfo {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub reserved: bool,
+    #[schema(value_type = Option<String>)]
+    pub owner: Option<Address>,
+    pub active: bool,
+    pub fee: u64,
+    pub ratio: u64,
+    pub stakers: u64,
+    pub total_staked: String,
+    pub self_stake: String,
+    pub delegated_stake: String,
+    pub owner_pending_unstakes: String,
+    /// Pending unstakes of the owner and every staker seen by the staking indexer; `null`
+    /// without the indexer or before it has reached the pool.
+    pub pending_unstakes: Option<String>,
+    /// The part of `pending_unstakes` still unbonding.
+    pub unbonding: Option<String>,
+    pub unbonding_period_blocks: u64,
+    pub failures: Vec<CallFailure>,
+}
+
+impl PoolStakingReading {
+    /// Sums pending unstakes whose unbonding period has not elapsed by `current_block`. Matured
+    /// unstakes are claimable and no longer count as unbonding.
+    pub fn unbonding_at(&self, current_block: U256) -> U256 {
+        self.unstakes
+            .iter()
+            .filter(|(_, block)| block.saturating_add(self.unbonding_period) > current_block)
+            .fold(U256::zero(), |acc, (amount, _)| acc.saturating_add(*amount))
+    }
+
+    pub fn to_info(&self, decimals: u8) -> PoolStakingInfo {
+        PoolStakingInfo {
+            address: self.address,
+            reserved: self.reserved,
+            owner: self.owner,
+            active: self.active,
+            fee: utils::saturating_u64(self.fee),
+            ratio: utils::saturating_u64(self.ratio),
+            stakers: utils::saturating_u64(self.stakers),
+            total_staked: utils::u256_to_human(self.total_stake, decimals),
+            self_stake: utils::u256_to_human(self.self_stake, decimals),
+            delegated_stake: utils::u256_to_human(self.total_stake.checked_sub(self.self_stake).unwrap_or(U256::zero()), decimals),
+            owner_pending_unstakes: utils::u256_to_human(self.owner_pending_unstakes, decimals),
+            pending_unstakes: self.pending_unstakes.map(|value| utils::u256_to_human(value, decimals)),
+            unbonding: self.unbonding.map(|value| utils::u256_to_human(value, decimals)),
+            unbonding_period_blocks: utils::saturating_u64(self.unbonding_period),
+            failures: self.failures.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StakingSummary {
+    pub block: u64,
+    pub pools: usize,
+    pub active_pools: usize,
+    pub stakers: u64,
+    pub total_staked: String,
+    pub self_stake: String,
+    pub delegated_stake: String,
+    pub owner_pending_unstakes: String,
+    /// `null` unless the stakers of every pool are known.
+    pub pending_unstakes: Option<String>,
+    pub unbonding: Option<String>,
+    pub failed_pools: usize,
+}
+
+pub fn summarize(readings: &[PoolStakingReading], block: u64, decimals: u8) -> StakingSummary {
+    let sum = |value: fn(&PoolStakingReading) -> U256| readings.iter().fold(U256::zero(), |acc, reading| acc.saturating_add(value(reading)));
+    let total_stake = sum(|reading| reading.total_stake);
+    let self_stake = sum(|reading| reading.self_stake);
+    let sum_known = |value: fn(&PoolStakingReading) -> Option<U256>| {
+        readings.iter().try_fold(U256::zero(), |acc, reading| value(reading).map(|value| acc.saturating_add(value)))
+    };
+
+    StakingSummary {
+        block,
+        pools: readings.len(),
+        active_pools: readings.iter().filter(|reading| reading.active).count(),
+        stakers: utils::saturating_u64(sum(|reading| reading.stakers)),
+        total_staked: utils::u256_to_human(total_stake, decimals),
+        self_stake: utils::u256_to_human(self_stake, decimals),
+        delegated_stake: utils::u256_to_human(total_stake.checked_sub(self_stake).unwrap_or(U256::zero()), decimals),
+        owner_pending_unstakes: utils::u256_to_human(sum(|reading| reading.owner_pending_unstakes), decimals),
+        pending_unstakes: sum_known(|reading| reading.pending_unstakes).map(|value| utils::u256_to_human(value, decimals)),
+        unbonding: sum_known(|reading| reading.unbonding).map(|value| utils::u256_to_human(value, decimals)),
+        failed_pools: readings.iter().filter(|reading| !reading.failures.is_empty()).count(),
+    }
+}
+
+async fn apply_reads<M: Middleware + 'static>(
+    client: Arc<M>,
+    multicall: &MulticallConfig,
+    readings: &mut [PoolStakingReading],
+    reads: Vec<(usize, StakingRead)>,
+) -> Result<(), anyhow::Error> {
+    let calls = reads
+        .iter()
+        .map(|&(i, read)| Ok((read.call(&StakingPool::new(readings[i].address, client.clone()))?, true)))
+        .collect::<Result<Vec<FallibleCall<M, Token>>, AbiError>>()?;
+    let results = multicall.try_call(client, calls).await?;
+    if results.len() != reads.len() {
+        return Err(anyhow::anyhow!("Expected {} staking results, got {}", reads.len(), results.len()));
+    }
+
+    for ((i, read), result) in reads.into_iter().zip(results) {
+        let reading = &mut readings[i];
+        match result {
+            Ok(token) => {
+                if read.apply(reading, token.clone()).is_none() {
+                    reading.failures.push(CallFailure {
+                        target: reading.address,
+                        function: read.name().to_string(),
+                        reason: format!("unexpected return value {:?}", token),
+                    });
+                }
+            }
+            Err(failure) => reading.failures.push(failure),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the staking state of every pool as of `current_block`. Pool-level values come first;
+/// the owner's stake and pending unstakes need the owner address and are read in a second round,
+/// together with the pending unstakes of every staker listed for the pool in `stakers`. Pools
+/// missing from `stakers` only get the owner's pending unstakes.
+pub async fn read_staking_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    current_block: u64,
+) -> Result<Vec<PoolStakingReading>, anyhow::Error> {
+    let pool_reads = [StakingRead::Owner, StakingRead::Active, StakingRead::Fee, StakingRead::Ratio, StakingRead::Stakers, StakingRead::TotalStake, StakingRead::UnbondingPeriod];
+    read_pools(client, pool_addresses, stakers, multicall, current_block, &pool_reads, true).await
+}
+
+/// Reads only what unbonding needs: the owner, `UNBONDING_PERIOD`, and the pending unstakes of the
+/// owner and every staker listed for the pool in `stakers`.
+pub async fn read_unbonding<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    current_block: u64,
+) -> Result<Vec<PoolStakingReading>, anyhow::Error> {
+    read_pools(client, pool_addresses, stakers, multicall, current_block, &[StakingRead::Owner, StakingRead::UnbondingPeriod], false).await
+}
+
+/// Reads only `totalStake` of every pool.
+pub async fn read_total_stake<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    multicall: &MulticallConfig,
+    current_block: u64,
+) -> Result<Vec<PoolStakingReading>, anyhow::Error> {
+    read_pools(client, pool_addresses, &HashMap::new(), multicall, current_block, &[StakingRead::TotalStake], false).await
+}
+
+// Reads `pool_reads` of every pool once, then the owner's stake if `self_stake` is set and the
+// pending unstakes of the owner and known stakers once the owner is known
+async fn read_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    current_block: u64,
+    pool_reads: &[StakingRead],
+    self_stake: bool,
+) -> Result<Vec<PoolStakingReading>, anyhow::Error> {
+    let mut readings: Vec<PoolStakingReading> = Vec::with_capacity(pool_addresses.len());
+    for &(address, reserved) in pool_addresses {
+        if readings.iter().any(|reading| reading.address == address) {
+            continue;
+        }
+        readings.push(PoolStakingReading { address, reserved, pending_unstakes: stakers.contains_key(&address).then(U256::zero), ..Default::default() });
+    }
+
+    let reads = (0..readings.len()).flat_map(|i| pool_reads.iter().map(move |&read| (i, read))).collect();
+    apply_reads(client.clone(), multicall, &mut readings, reads).await?;
+
+    let mut account_reads = Vec::new();
+    for (i, reading) in readings.iter().enumerate() {
+        if let Some(owner) = reading.owner {
+            if self_stake {
+                account_reads.push((i, StakingRead::CurrentStake(owner)));
+            }
+            account_reads.push((i, StakingRead::PendingUnstakes(owner)));
+        }
+        for &staker in stakers.get(&reading.address).into_iter().flatten() {
+            if Some(staker) != reading.owner {
+                account_reads.push((i, StakingRead::PendingUnstakes(staker)));
+            }
+        }
+    }
+    apply_reads(client, multicall, &mut readings, account_reads).await?;
+
+    for reading in &mut readings {
+        if reading.pending_unstakes.is_some() {
+            reading.unbonding = Some(reading.unbonding_at(U256::from(current_block)));
+        }
+    }
+
+    Ok(readings)
+}
+
+pub async fn get_staking_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    decimals: u8,
+) -> Result<Vec<PoolStakingInfo>, anyhow::Error> {
+    let block = client.get_block_number().await?.as_u64();
+    let readings = read_staking_pools(client, pool_addresses, stakers, multicall, block).await?;
+    Ok(readings.iter().map(|reading| reading.to_info(decimals)).collect())
+}
+
+pub async fn get_staking_summary<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool_addresses: &[(Address, bool)],
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    decimals: u8,
+) -> Result<StakingSummary, anyhow::Error> {
+    let block = client.get_block_number().await?.as_u64();
+    let readings = read_staking_pools(client, pool_addresses, stakers, multicall, block).await?;
+    Ok(summarize(&readings, block, decimals))
+}
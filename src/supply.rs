@@ -1,8 +1,21 @@
-use crate::{ERC20, StakingPool, utils};
-use ethers::contract::Multicall;
+use crate::call_plan::SupplyCallPlan;
+use crate::config::{self, AllocationLabel, ExcludedAddress, PoolSchedule};
+use crate::multicall::{CallFailure, MulticallConfig};
+use crate::vesting::{CliffOnly, Elapsed, Linear, Piecewise, Schedule, Stepped};
+use crate::{ERC20, staking, utils};
 use ethers::providers::Middleware;
 use ethers::types::{Address, U256};
+use serde::Serialize;
+use utoipa::ToSchema;
 use std::cmp;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub use crate::vesting::{ReleaseGranularity, TimeUnit};
+
+// Matchain produces a block every 0.5 seconds
+pub const DEFAULT_BLOCK_TIME_MS: u64 = 500;
 
 #[derive(Debug, Clone)]
 pub struct PoolCalculation {
@@ -10,269 +23,973 @@ pub struct PoolCalculation {
     pub ratio_precision: U256,
     pub locked_amount: U256,
     pub days_passed: U256,
+    pub cliff_days: U256,
+    pub vesting_days: U256,
     pub days_until_lock_ends: U256,
     pub days_until_vesting_ends: U256,
     pub unlocked_fraction: U256,
+    pub time_unit: TimeUnit,
+    pub granularity: ReleaseGranularity,
 }
 
+/// Computes the locked portion of `initial`. `current` and `start` are measured in `time_unit`
+/// (timestamps for [`TimeUnit::Seconds`], block numbers otherwise); `cliff` and `vesting` are
+/// in days.
+#[allow(clippy::too_many_arguments)]
 pub fn calculate_pool_vesting(
     initial: U256,
     tge_percentage: U256,
     cliff: U256,
     vesting: U256,
     ratio_precision: U256,
-    current_ts: U256,
-    tge_ts: U256,
+    current: U256,
+    start: U256,
+    time_unit: TimeUnit,
+    granularity: ReleaseGranularity,
     vesting_type: &str,
 ) -> PoolCalculation {
-    let chain_units_passed = current_ts.checked_sub(tge_ts).unwrap_or(U256::zero());
-    // Chain operates at 0.5 seconds per block ⇒ 172,800 blocks per day
-    let days_passed = chain_units_passed / U256::from(172800u64);
-
-    let days_until_lock_ends = cliff.checked_sub(days_passed).unwrap_or(U256::zero());
-
-    let total_vesting_period = cliff.checked_add(vesting).unwrap_or(cliff);
-    let days_until_vesting_ends = total_vesting_period.checked_sub(days_passed).unwrap_or(U256::zero());
+    let units_per_day = time_unit.units_per_day();
+    let (cliff, vesting) = (cliff.saturating_mul(units_per_day), vesting.saturating_mul(units_per_day));
+    let schedule: Box<dyn Schedule> = match vesting_type {
+        "stepped" => Box::new(Stepped::quarterly(tge_percentage, cliff, units_per_day)),
+        "cliff" => Box::new(CliffOnly { tge_percentage, cliff }),
+        _ => Box::new(Linear { tge_percentage, cliff, vesting }),
+    };
+    calculate_vesting(initial, cliff, vesting, ratio_precision, current, start, time_unit, granularity, schedule.as_ref())
+}
 
-    let unlocked_fraction = if vesting_type == "stepped" {
-        if days_passed < cliff {
-            tge_percentage * (ratio_precision / U256::from(100u64))
-        } else {
-            let periods_passed = (days_passed.checked_sub(cliff).unwrap_or(U256::zero())) / U256::from(90);
-            let periods = cmp::min(periods_passed, U256::from(6));
-            let step_percentage = U256::from(166700);
-            let tge_scaled = tge_percentage * (ratio_precision / U256::from(100u64));
-            let mut remaining = ratio_precision.checked_sub(tge_scaled).unwrap_or(U256::zero());
-            let mut unlocked = tge_scaled;
-            for _ in 0..periods.as_u64() {
-                let release = (remaining * step_percentage) / ratio_precision;
-                unlocked = unlocked.checked_add(release).unwrap_or(unlocked);
-                remaining = remaining.checked_sub(release).unwrap_or(remaining);
-            }
-            cmp::min(unlocked, ratio_precision)
-        }
+/// Vesting of a wallet schedule at `current`, counted in seconds from the schedule's start
+/// (`tge_timestamp` unless it has its own). Nothing is unlocked before the start. Custom
+/// schedules unlock by their tranches, with the first tranche standing in for the cliff and the
+/// last for the end of vesting.
+pub fn calculate_schedule_vesting(schedule: &PoolSchedule, ratio_precision: U256, current: U256, tge_timestamp: U256) -> PoolCalculation {
+    let start = schedule.start(tge_timestamp);
+    let calc = if schedule.vesting_type != "custom" {
+        calculate_pool_vesting(
+            schedule.balance_at_tge,
+            schedule.tge_percentage,
+            schedule.cliff,
+            schedule.vesting,
+            ratio_precision,
+            current,
+            start,
+            TimeUnit::Seconds,
+            schedule.release_granularity,
+            &schedule.vesting_type,
+        )
     } else {
-        // Linear vesting
-        if days_passed < cliff {
-            tge_percentage * (ratio_precision / U256::from(100u64))
-        } else if vesting > U256::zero() {
-            let vesting_progress = days_passed.checked_sub(cliff).unwrap_or(U256::zero());
-            let frac = (vesting_progress.checked_mul(ratio_precision).unwrap_or(U256::zero()) / vesting)
-                .checked_add(tge_percentage * (ratio_precision / U256::from(100u64)))
-                .unwrap_or(tge_percentage * (ratio_precision / U256::from(100u64)));
-            cmp::min(frac, ratio_precision)
-        } else {
-            tge_percentage * (ratio_precision / U256::from(100u64))
-        }
+        let tranches = schedule.tranche_offsets(tge_timestamp);
+        let (cliff, vesting) = match (tranches.first(), tranches.last()) {
+            // Config validation sorts out unordered tranches, but other callers may pass them
+            (Some(&(first, _)), Some(&(last, _))) => (first, last.saturating_sub(first)),
+            _ => (U256::zero(), U256::zero()),
+        };
+        let piecewise = Piecewise::tranches(&tranches);
+        calculate_vesting(schedule.balance_at_tge, cliff, vesting, ratio_precision, current, start, TimeUnit::Seconds, schedule.release_granularity, &piecewise)
+    };
+    if current < start {
+        return PoolCalculation { locked_amount: schedule.balance_at_tge, unlocked_fraction: U256::zero(), ..calc };
+    }
+    calc
+}
+
+// `units` in whole days, rounded down or up; zero when a day has no units
+fn units_to_days(units: U256, units_per_day: U256, round_up: bool) -> U256 {
+    let Some(days) = units.checked_div(units_per_day) else {
+        return U256::zero();
     };
+    if round_up && days * units_per_day < units { days + 1 } else { days }
+}
 
-    let unlocked = (initial * unlocked_fraction) / ratio_precision;
+// `cliff` and `vesting` are in `time_unit`; the calculation reports them in days
+#[allow(clippy::too_many_arguments)]
+fn calculate_vesting(
+    initial: U256,
+    cliff: U256,
+    vesting: U256,
+    ratio_precision: U256,
+    current: U256,
+    start: U256,
+    time_unit: TimeUnit,
+    granularity: ReleaseGranularity,
+    schedule: &dyn Schedule,
+) -> PoolCalculation {
+    let elapsed = Elapsed::between(start, current, time_unit, granularity);
+    let units_per_day = elapsed.units_per_day;
+    let days_until_lock_ends = units_to_days(cliff.saturating_sub(elapsed.units), units_per_day, true);
+    let days_until_vesting_ends = units_to_days(cliff.saturating_add(vesting).saturating_sub(elapsed.units), units_per_day, true);
 
-    let locked = initial.checked_sub(unlocked).unwrap_or(U256::zero());
+    let unlocked_fraction = schedule.unlocked_fraction(elapsed, ratio_precision);
+    let locked = schedule.locked(initial, elapsed, ratio_precision);
 
     PoolCalculation {
         initial,
         ratio_precision,
         locked_amount: locked,
-        days_passed,
+        days_passed: elapsed.days(),
+        cliff_days: units_to_days(cliff, units_per_day, false),
+        vesting_days: units_to_days(vesting, units_per_day, false),
         days_until_lock_ends,
         days_until_vesting_ends,
         unlocked_fraction,
+        time_unit,
+        granularity,
     }
 }
 
 pub async fn get_total_supply(
     matchain_contract: &ERC20<impl Middleware + Clone + 'static>,
     bsc_contract: &ERC20<impl Middleware + Clone + 'static>,
+    matchain_multicall: &MulticallConfig,
+    bsc_multicall: &MulticallConfig,
     decimals: u8,
 ) -> Result<String, anyhow::Error> {
-    let mut matchain_multicall = Multicall::new(matchain_contract.client(), Some("0xcA11bde05977b3631167028862bE2a173976CA11".parse::<Address>().unwrap())).await?;
-    matchain_multicall.add_call(matchain_contract.total_supply(), false);
-    matchain_multicall.add_call(matchain_contract.balance_of(Address::zero()), false);
-    let matchain_results: Vec<U256> = matchain_multicall.call_array().await?;
+    let value = get_total_supply_wei(matchain_contract, bsc_contract, matchain_multicall, bsc_multicall).await?;
+    Ok(utils::u256_to_human(value, decimals))
+}
+
+/// Total supply across Matchain and BSC in wei, net of tokens burned to the zero address.
+pub async fn get_total_supply_wei(
+    matchain_contract: &ERC20<impl Middleware + Clone + 'static>,
+    bsc_contract: &ERC20<impl Middleware + Clone + 'static>,
+    matchain_multicall: &MulticallConfig,
+    bsc_multicall: &MulticallConfig,
+) -> Result<U256, anyhow::Error> {
+    let matchain_calls = vec![matchain_contract.total_supply(), matchain_contract.balance_of(Address::zero())];
+    let matchain_results = matchain_multicall.call_u256(matchain_contract.client(), matchain_calls).await?;
 
-    let mut bsc_multicall = Multicall::new(bsc_contract.client(), Some("0xcA11bde05977b3631167028862bE2a173976CA11".parse::<Address>().unwrap())).await?;
-    bsc_multicall.add_call(bsc_contract.total_supply(), false);
-    bsc_multicall.add_call(bsc_contract.balance_of(Address::zero()), false);
-    let bsc_results: Vec<U256> = bsc_multicall.call_array().await?;
+    let bsc_calls = vec![bsc_contract.total_supply(), bsc_contract.balance_of(Address::zero())];
+    let bsc_results = bsc_multicall.call_u256(bsc_contract.client(), bsc_calls).await?;
 
-    let total_m = matchain_results[0];
-    let burn_m = matchain_results[1];
-    let total_b = bsc_results[0];
-    let burn_b = bsc_results[1];
+    let (&[total_m, burn_m], &[total_b, burn_b]) = (&matchain_results[..], &bsc_results[..]) else {
+        return Err(anyhow::anyhow!("Expected 2 total supply results per chain, got {} on Matchain and {} on BSC", matchain_results.len(), bsc_results.len()));
+    };
 
     let value = (total_m.checked_sub(burn_m).unwrap_or(U256::zero())) + (total_b.checked_sub(burn_b).unwrap_or(U256::zero()));
     eprintln!("Total Supply: Matchain = {}, BSC = {}, Burned = {}, Value = {}", total_m, total_b, burn_m + burn_b, value);
 
-    Ok(utils::u256_to_human(value, decimals))
+    Ok(value)
 }
 
-pub async fn get_circulating_supply(
-    matchain_contract: &ERC20<impl Middleware + Clone + 'static>,
-    excluded_addresses: &[(Address, String)],
-    pool_data: &[(Vec<(Address, String)>, U256, U256, U256, String, U256)],
+/// Where the max supply comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxSupply {
+    /// No max supply is reported.
+    Unset,
+    /// A fixed amount in wei.
+    Fixed(U256),
+    /// Read from the Matchain token's `cap()`.
+    Cap,
+}
+
+impl MaxSupply {
+    /// Parses a max supply setting: `cap` reads it on-chain, anything else is an amount in whole
+    /// tokens with at most `decimals` decimal places.
+    pub fn parse(value: &str, decimals: u8) -> Result<Self, anyhow::Error> {
+        match value.trim() {
+            value if value.eq_ignore_ascii_case("cap") => Ok(Self::Cap),
+            value => {
+                // parse_units would accept negative amounts and silently drop excess decimals
+                let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+                if whole.len() + fraction.len() == 0 || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) || fraction.len() > decimals as usize {
+                    return Err(anyhow::anyhow!("Invalid max supply '{}': expected `cap` or a token amount with at most {} decimals", value, decimals));
+                }
+                Ok(Self::Fixed(ethers::utils::parse_units(value, decimals as u32)?.into()))
+            }
+        }
+    }
+}
+
+pub async fn get_max_supply<M: Middleware + 'static>(matchain_contract: &ERC20<M>, max_supply: MaxSupply) -> Result<Option<U256>, anyhow::Error> {
+    match max_supply {
+        MaxSupply::Unset => Ok(None),
+        MaxSupply::Fixed(value) => Ok(Some(value)),
+        MaxSupply::Cap => Ok(Some(matchain_contract.cap().call().await.map_err(|e| anyhow::anyhow!("Failed to read cap() from the token: {}", e))?)),
+    }
+}
+
+/// What can still be minted in wei, or zero if more has been minted than the max supply; `None`
+/// without a max supply. A fixed max supply is compared with the total supply across both chains
+/// net of burns. `cap()` only limits minting on Matchain, so it is compared with Matchain's
+/// `totalSupply()`, burned tokens included, since burning does not free up room under the cap.
+pub async fn get_mintable_supply_wei<M: Middleware + Clone + 'static>(
+    matchain_contract: &ERC20<M>,
+    bsc_contract: &ERC20<impl Middleware + Clone + 'static>,
+    matchain_multicall: &MulticallConfig,
+    bsc_multicall: &MulticallConfig,
+    max_supply: MaxSupply,
+) -> Result<Option<U256>, anyhow::Error> {
+    let (max, minted) = match max_supply {
+        MaxSupply::Unset => return Ok(None),
+        MaxSupply::Fixed(max) => (max, get_total_supply_wei(matchain_contract, bsc_contract, matchain_multicall, bsc_multicall).await?),
+        MaxSupply::Cap => {
+            let minted = async { matchain_contract.total_supply().call().await.map_err(|e| anyhow::anyhow!("Failed to read totalSupply() from the token: {}", e)) };
+            let (cap, minted) = tokio::try_join!(get_max_supply(matchain_contract, max_supply), minted)?;
+            (cap.unwrap_or_default(), minted)
+        }
+    };
+    Ok(Some(max.saturating_sub(minted)))
+}
+
+/// What to do with a StakingPool whose vesting parameters could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolFailurePolicy {
+    /// Leave the pool out of the locked balance.
+    Skip,
+    /// Count the pool's whole token balance as locked.
+    Locked,
+    /// Fail the circulating supply request.
+    Fail,
+}
+
+impl FromStr for PoolFailurePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "locked" => Ok(Self::Locked),
+            "fail" => Ok(Self::Fail),
+            other => Err(anyhow::anyhow!("Unknown pool failure policy '{}', expected skip, locked or fail", other)),
+        }
+    }
+}
+
+/// Where StakingPool vesting schedules come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolVestingMode {
+    /// Rebuild the schedule from the raw lock/vesting block periods, starting at the block
+    /// estimated for TGE and counting days with the configured block time.
+    Replay,
+    /// Use `getVestingSchedule`, starting at the pool's `poolCreation` block and counting days
+    /// with the contract's own `BLOCKS_PER_DAY`.
+    Contract,
+}
+
+impl FromStr for PoolVestingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "replay" => Ok(Self::Replay),
+            "contract" => Ok(Self::Contract),
+            other => Err(anyhow::anyhow!("Unknown pool vesting mode '{}', expected replay or contract", other)),
+        }
+    }
+}
+
+/// Whether tokens waiting out a StakingPool's unbonding period get their own breakdown bucket,
+/// and if so whether they count as circulating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnbondingTreatment {
+    /// Unbonding tokens stay in circulating supply and are not reported separately.
+    Off,
+    /// Report unbonding tokens and keep them in circulating supply.
+    Circulating,
+    /// Report unbonding tokens and subtract them from circulating supply.
+    Excluded,
+}
+
+impl FromStr for UnbondingTreatment {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "circulating" => Ok(Self::Circulating),
+            "excluded" => Ok(Self::Excluded),
+            other => Err(anyhow::anyhow!("Unknown unbonding treatment '{}', expected off, circulating or excluded", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SupplyOptions {
+    pub tge_timestamp: U256,
+    pub pool_failure_policy: PoolFailurePolicy,
+    pub pool_vesting_mode: PoolVestingMode,
+    /// Compare the computed unlocked self-stake with each pool's `getAvailableSelfStake`.
+    pub pool_cross_check: bool,
+    /// Block time used to count days for pools replayed from raw block periods.
+    pub block_time_ms: u64,
+    /// Release granularity applied to StakingPool vesting.
+    pub pool_release_granularity: ReleaseGranularity,
+    pub unbonding: UnbondingTreatment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolStatus {
+    Vesting,
+    Reserved,
+    Skipped,
+    AssumedLocked,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ScheduleBreakdown {
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Address>,
+    #[serde(flatten)]
+    pub label: AllocationLabel,
+    pub vesting_type: String,
+    /// Unix timestamp vesting counts from: the schedule's own start, or TGE.
+    pub start_timestamp: u64,
+    pub tge_percentage: u64,
+    pub cliff_days: u64,
+    pub vesting_days: u64,
+    pub time_unit: TimeUnit,
+    pub release_granularity: ReleaseGranularity,
+    pub days_passed: u64,
+    pub days_until_lock_ends: u64,
+    pub days_until_vesting_ends: u64,
+    pub initial: String,
+    /// Locked by the schedule, capped at `balance`.
+    pub locked: String,
+    pub unlocked_percent: u64,
+    /// Combined balance of the schedule's Matchain wallets; `None` if it has none.
+    pub balance: Option<String>,
+    pub discrepancy: Option<ScheduleDiscrepancy>,
+    #[serde(skip)]
+    pub initial_wei: U256,
+    #[serde(skip)]
+    pub locked_wei: U256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// The wallets hold less than the schedule still locks; the locked amount is capped at the
+    /// balance.
+    BelowLocked,
+    /// The wallets hold more than the configured allocation.
+    AboveAllocation,
+}
+
+/// A vesting wallet balance outside the range its schedule allows: at least the locked amount,
+/// at most the configured `balance_at_tge`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ScheduleDiscrepancy {
+    pub kind: DiscrepancyKind,
+    /// The locked amount or the allocation, depending on `kind`.
+    pub expected: String,
+    pub difference: String,
+}
+
+impl ScheduleDiscrepancy {
+    pub fn check(initial: U256, locked: U256, balance: U256, decimals: u8) -> Option<Self> {
+        let (kind, expected, difference) = if balance < locked {
+            (DiscrepancyKind::BelowLocked, locked, locked - balance)
+        } else if balance > initial {
+            (DiscrepancyKind::AboveAllocation, initial, balance - initial)
+        } else {
+            return None;
+        };
+        Some(Self { kind, expected: utils::u256_to_human(expected, decimals), difference: utils::u256_to_human(difference, decimals) })
+    }
+}
+
+/// Combined balance of a schedule's Matchain wallets, or `None` if it has no Matchain wallet.
+pub fn schedule_balance(schedule: &PoolSchedule, balances: &[(Address, U256)]) -> Option<U256> {
+    let mut wallets = schedule.addresses.iter().filter(|(_, chain)| chain == "Matchain").peekable();
+    wallets.peek()?;
+    let mut seen = Vec::new();
+    let mut total = U256::zero();
+    for (addr, _) in wallets {
+        if seen.contains(addr) {
+            continue;
+        }
+        seen.push(*addr);
+        let balance = balances.iter().find(|(wallet, _)| wallet == addr).map_or(U256::zero(), |&(_, balance)| balance);
+        total = total.saturating_add(balance);
+    }
+    Some(total)
+}
+
+/// Balance of one Matchain excluded address.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExcludedBreakdown {
+    #[schema(value_type = String)]
+    pub address: Address,
+    #[serde(flatten)]
+    pub label: AllocationLabel,
+    pub balance: String,
+    #[serde(skip)]
+    pub balance_wei: U256,
+}
+
+/// Wallet schedules and excluded addresses summed per allocation category, in the order the
+/// categories first appear in the config.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CategoryBreakdown {
+    pub category: String,
+    /// Initial balance of the category's vesting schedules.
+    pub allocated: String,
+    pub locked: String,
+    pub unlocked: String,
+    /// Balance held by the category's excluded addresses.
+    pub excluded_balance: String,
+    #[serde(skip)]
+    pub allocated_wei: U256,
+    #[serde(skip)]
+    pub locked_wei: U256,
+    #[serde(skip)]
+    pub excluded_balance_wei: U256,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolCrossCheck {
+    /// Initial self-stake minus the computed locked amount.
+    pub computed_available: String,
+    /// `getAvailableSelfStake`, or `None` if the call failed.
+    pub contract_available: Option<String>,
+    /// Contract value minus computed value.
+    pub difference: Option<String>,
+    pub matches: bool,
+    /// The failed `getAvailableSelfStake` call. Kept here rather than in the breakdown's
+    /// `failures`, since the cross-check does not affect the supply figures.
+    pub failure: Option<CallFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolBreakdown {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub status: PoolStatus,
+    pub schedule_source: PoolVestingMode,
+    /// Block the schedule is counted from: estimated TGE block in replay mode, `poolCreation`
+    /// in contract mode.
+    pub start: u64,
+    /// Lock and vesting periods in whole days, rounded down. Vesting itself uses the exact
+    /// periods, which in replay mode are block counts.
+    pub lock_days: u64,
+    pub vesting_days: u64,
+    pub time_unit: Option<TimeUnit>,
+    pub release_granularity: ReleaseGranularity,
+    pub days_passed: u64,
+    pub initial: String,
+    pub locked: String,
+    pub unlocked_percent: u64,
+    pub cross_check: Option<PoolCrossCheck>,
+}
+
+// Vesting inputs for one on-chain pool, normalised across schedule sources
+struct PoolVestingInput {
+    address: Address,
+    status: PoolStatus,
+    initial: U256,
+    // Lock and vesting periods in `time_unit`
+    lock: U256,
+    vesting: U256,
+    ratio_precision: U256,
+    start: U256,
+    now: U256,
+    time_unit: TimeUnit,
+    available_self_stake: Option<Result<U256, CallFailure>>,
+}
+
+/// MAT staked across all StakingPool contracts and its share of supply.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StakedSupply {
+    pub total_staked: String,
+    /// Can exceed 100%: stake includes pool self-stake that is still locked and so not part of
+    /// circulating supply.
+    pub staked_percent_of_circulating: String,
+    pub staked_percent_of_total: String,
+    pub pools: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StakedSupplyReport {
+    pub block: u64,
+    pub total_supply: String,
+    pub circulating_supply: String,
+    #[serde(flatten)]
+    pub staked: StakedSupply,
+    /// Pools whose `totalStake` could not be read and are left out of `total_staked`.
+    pub failures: Vec<CallFailure>,
+}
+
+impl StakedSupplyReport {
+    pub fn new(breakdown: SupplyBreakdown, staked: StakedSupply, failures: Vec<CallFailure>) -> Self {
+        Self {
+            block: breakdown.block,
+            total_supply: breakdown.total_supply,
+            circulating_supply: breakdown.circulating_supply,
+            staked,
+            failures,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SupplyBreakdown {
+    pub block: u64,
+    pub timestamp: u64,
+    pub total_supply: String,
+    pub excluded_balance: String,
+    pub locked_balance: String,
+    /// Pending unstakes still in their unbonding period; `None` when unbonding is not tracked.
+    pub unbonding_balance: Option<String>,
+    pub unbonding_treatment: UnbondingTreatment,
+    /// Pools the staking indexer has not reached yet, whose `unbonding_balance` share only
+    /// counts the owner's unstakes.
+    #[schema(value_type = Vec<String>)]
+    pub unbonding_incomplete: Vec<Address>,
+    pub circulating_supply: String,
+    #[serde(skip)]
+    pub total_supply_wei: U256,
+    #[serde(skip)]
+    pub circulating_supply_wei: U256,
+    /// Matchain tokens held by the zero address, already netted out of `total_supply`.
+    #[serde(skip)]
+    pub burn_balance_wei: U256,
+    /// Staked supply at the breakdown's supply figures; `None` unless added with
+    /// [`get_staked_supply`] and the staking reads succeeded.
+    pub staked: Option<StakedSupply>,
+    pub categories: Vec<CategoryBreakdown>,
+    pub excluded: Vec<ExcludedBreakdown>,
+    pub schedules: Vec<ScheduleBreakdown>,
+    pub pools: Vec<PoolBreakdown>,
+    /// Failed reads that affect the supply figures.
+    pub failures: Vec<CallFailure>,
+    /// Failed staking reads, which only affect `staked`.
+    pub staking_failures: Vec<CallFailure>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn get_circulating_supply<M: Middleware + Clone + 'static>(
+    matchain_contract: &ERC20<M>,
+    excluded_addresses: &[ExcludedAddress],
+    pool_data: &[PoolSchedule],
     onchain_pool_addresses: &[(Address, bool)],
-    tge_timestamp: U256,
+    stakers: &HashMap<Address, Vec<Address>>,
+    multicall: &MulticallConfig,
+    options: &SupplyOptions,
     decimals: u8,
-) -> Result<String, anyhow::Error> {
+) -> Result<SupplyBreakdown, anyhow::Error> {
     let current_block = matchain_contract.client().get_block_number().await?;
     let current_ts = matchain_contract.client().get_block(current_block).await?.map(|block| block.timestamp).unwrap_or(U256::zero());
     eprintln!("Current Block: {}, Current TS: {}", current_block, current_ts);
 
     let pool_addresses: Vec<Address> = pool_data
         .iter()
-        .flat_map(|(addrs, _, _, _, _, _)| addrs.iter().map(|(addr, _)| *addr))
+        .flat_map(|schedule| schedule.addresses.iter().map(|(addr, _)| *addr))
         .collect();
     let total_excluded_count = excluded_addresses.len();
-    let excluded_matchain: Vec<(Address, String)> = excluded_addresses
+    let excluded_matchain: Vec<&ExcludedAddress> = excluded_addresses
         .iter()
-        .filter(|&(_, chain)| chain == "Matchain")
-        .cloned()
+        .filter(|excluded| excluded.chain == "Matchain")
         .collect();
     let unique_excluded_addresses: Vec<Address> = excluded_matchain
         .iter()
-        .map(|(addr, _)| *addr)
+        .map(|excluded| excluded.address)
         .filter(|addr| !pool_addresses.contains(addr))
         .collect();
 
-    let mut matchain_multicall = Multicall::new(matchain_contract.client(), Some("0xcA11bde05977b3631167028862bE2a173976CA11".parse::<Address>().unwrap())).await?;
-    matchain_multicall.add_call(matchain_contract.total_supply(), false);
-    matchain_multicall.add_call(matchain_contract.balance_of(Address::zero()), false);
+    // Vesting wallets are read so locked amounts can be reconciled with what they actually hold
+    let schedule_wallets = config::matchain_schedule_wallets(pool_data);
 
-    for &addr in &unique_excluded_addresses {
-        matchain_multicall.add_call(matchain_contract.balance_of(addr), false);
-    }
+    let plan = SupplyCallPlan::new(&unique_excluded_addresses, &schedule_wallets, onchain_pool_addresses, options.pool_vesting_mode, options.pool_cross_check);
+    let matchain_results = multicall.try_call(matchain_contract.client(), plan.calls(matchain_contract)?).await?;
+    eprintln!("Matchain Results (length={}): {:?}", matchain_results.len(), matchain_results);
+    eprintln!("Excluded addresses: total={}, matchain_only={}, filtered_out_as_pools={}", total_excluded_count, excluded_matchain.len(), excluded_matchain.len() - unique_excluded_addresses.len());
+    let readings = plan.decode(matchain_results)?;
+
+    // Estimate the TGE block from the configured block time for schedules counted in blocks
+    let seconds_since_tge = current_ts.checked_sub(options.tge_timestamp).unwrap_or(U256::zero());
+    let blocks_since_tge = seconds_since_tge * U256::from(1000u64) / U256::from(options.block_time_ms.max(1));
+    let tge_block = U256::from(current_block.as_u64()).checked_sub(blocks_since_tge).unwrap_or(U256::zero());
 
-    for &(addr, reserved) in onchain_pool_addresses.iter() {
-        let pool = StakingPool::new(addr, matchain_contract.client().clone());
-        if reserved {
-            matchain_multicall.add_call(matchain_contract.balance_of(addr), false);
+    let mut failures = Vec::new();
+    let mut onchain_pool_data: Vec<PoolVestingInput> = Vec::new();
+    for mut pool in readings.pools {
+        // Unlocked shares are divided by RATIO_PRECISION, so a zero makes the pool unreadable.
+        // Contract mode also counts whole days of BLOCKS_PER_DAY blocks from the poolCreation
+        // block, so values that cannot be a block count or a past block do too
+        let contract_mode = options.pool_vesting_mode == PoolVestingMode::Contract;
+        if !pool.reserved && pool.failures.is_empty() {
+            if pool.ratio_precision.is_zero() {
+                pool.failures.push(CallFailure { target: pool.address, function: "RATIO_PRECISION".to_string(), reason: "returned zero".to_string() });
+            } else if contract_mode && pool.blocks_per_day.is_zero() {
+                pool.failures.push(CallFailure { target: pool.address, function: "BLOCKS_PER_DAY".to_string(), reason: "returned zero".to_string() });
+            } else if contract_mode && pool.pool_creation > U256::from(current_block.as_u64()) {
+                let reason = format!("block {} is after the current block {}", pool.pool_creation, current_block);
+                pool.failures.push(CallFailure { target: pool.address, function: "poolCreation".to_string(), reason });
+            }
+        }
+        let mut input = PoolVestingInput {
+            address: pool.address,
+            status: PoolStatus::Vesting,
+            initial: U256::zero(),
+            lock: U256::zero(),
+            vesting: U256::zero(),
+            ratio_precision: pool.ratio_precision,
+            start: U256::zero(),
+            now: U256::zero(),
+            time_unit: TimeUnit::OnchainBlocks { blocks_per_day: 0 },
+            available_self_stake: pool.available_self_stake,
+        };
+        if pool.reserved {
+            input.status = PoolStatus::Reserved;
+            input.initial = pool.balance;
+        } else if pool.failures.is_empty() {
+            input.initial = pool.initial_stake;
+            match options.pool_vesting_mode {
+                PoolVestingMode::Replay => {
+                    // On-chain periods are block counts, so elapsed time is counted in blocks too
+                    input.time_unit = TimeUnit::Blocks { block_time_ms: options.block_time_ms };
+                    input.lock = pool.lock_period;
+                    input.vesting = pool.vesting_duration;
+                    input.start = tge_block;
+                    input.now = U256::from(current_block.as_u64());
+                }
+                PoolVestingMode::Contract => {
+                    input.time_unit = TimeUnit::OnchainBlocks { blocks_per_day: utils::saturating_u64(pool.blocks_per_day) };
+                    input.lock = pool.lock_days.saturating_mul(input.time_unit.units_per_day());
+                    input.vesting = pool.vesting_days.saturating_mul(input.time_unit.units_per_day());
+                    input.start = pool.pool_creation;
+                    input.now = U256::from(current_block.as_u64());
+                }
+            }
         } else {
-            matchain_multicall.add_call(pool.initial_self_stake_amount(), false);
-            matchain_multicall.add_call(pool.initial_lock_period(), false);
-            matchain_multicall.add_call(pool.vesting_duration(), false);
-            matchain_multicall.add_call(pool.ratio_precision(), false);
+            for failure in &pool.failures {
+                eprintln!("Pool call failed ({:?} policy): {}", options.pool_failure_policy, failure);
+            }
+            if options.pool_failure_policy == PoolFailurePolicy::Fail {
+                return Err(anyhow::anyhow!("Failed to read vesting parameters for pool {:?}: {}", pool.address, pool.failures[0]));
+            }
+            input.status = if options.pool_failure_policy == PoolFailurePolicy::Locked { PoolStatus::AssumedLocked } else { PoolStatus::Skipped };
+            failures.extend(pool.failures);
         }
+        onchain_pool_data.push(input);
     }
 
-    let matchain_results: Vec<U256> = matchain_multicall.call_array().await?;
-    eprintln!("Matchain Results (length={}): {:?}", matchain_results.len(), matchain_results);
-    eprintln!("Excluded addresses: total={}, matchain_only={}, filtered_out_as_pools={}", total_excluded_count, excluded_matchain.len(), excluded_matchain.len() - unique_excluded_addresses.len());
-
-    let mut m_iter = matchain_results.into_iter();
-    let total_m = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing Matchain total supply"))?;
-    let burn_m = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing Matchain burn balance"))?;
-    let excluded_balances: Vec<U256> = (0..unique_excluded_addresses.len())
-        .map(|_| m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing excluded balance")).unwrap())
-        .collect();
-
-    let onchain_pool_data: Vec<(U256, U256, U256, U256, bool)> = onchain_pool_addresses
+    // Pools treated as fully locked fall back to their token balance, which does not depend on the pool contract
+    let assumed_locked: Vec<usize> = onchain_pool_data
         .iter()
-        .map(|&(addr, reserved)| {
-            if reserved {
-                let full_balance = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing full balance for reserved pool {}", addr)).unwrap();
-                (full_balance, U256::zero(), U256::zero(), U256::zero(), reserved)
-            } else {
-                let initial = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing initial stake for pool {}", addr)).unwrap();
-                let lock_seconds = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing lock period for pool {}", addr)).unwrap();
-                let vesting_seconds = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing vesting duration for pool {}", addr)).unwrap();
-                let ratio_precision = m_iter.next().ok_or_else(|| anyhow::anyhow!("Missing ratio precision for pool {}", addr)).unwrap();
-                // On-chain values are in blocks; convert to days using 0.5s/block
-                let lock_days = lock_seconds / U256::from(172800u64);
-                let vesting_days = vesting_seconds / U256::from(172800u64);
-                (initial, lock_days, vesting_days, ratio_precision, reserved)
-            }
-        })
+        .enumerate()
+        .filter(|(_, input)| input.status == PoolStatus::AssumedLocked)
+        .map(|(i, _)| i)
         .collect();
+    if !assumed_locked.is_empty() {
+        let balance_calls = assumed_locked.iter().map(|&i| matchain_contract.balance_of(onchain_pool_data[i].address)).collect();
+        let balances = multicall.call_u256(matchain_contract.client(), balance_calls).await?;
+        for (i, balance) in assumed_locked.into_iter().zip(balances) {
+            onchain_pool_data[i].initial = balance;
+        }
+    }
 
-    let total_supply = total_m.checked_sub(burn_m).unwrap_or(U256::zero());
-    let excluded_balance = excluded_balances.iter().fold(U256::zero(), |acc, &b| acc + b);
+    let total_supply = readings.total_supply.checked_sub(readings.burn_balance).unwrap_or(U256::zero());
+    let excluded_balance = readings.excluded_balances.iter().fold(U256::zero(), |acc, &(_, b)| acc + b);
 
     let ratio_precision = U256::from(1_000_000u64);
     let mut locked_balance = U256::zero();
-    let mut wallet_details = Vec::new();
-    let mut pool_details = Vec::new();
-
-    for (addrs, tge_percentage, cliff, vesting, vesting_type, balance_at_tge) in pool_data {
-        let initial = *balance_at_tge;
-        let calc = calculate_pool_vesting(
-            initial,
-            *tge_percentage,
-            *cliff,
-            *vesting,
-            ratio_precision,
-            current_ts,
-            tge_timestamp,
-            vesting_type,
-        );
-        locked_balance = locked_balance.checked_add(calc.locked_amount).unwrap_or(locked_balance);
-        let unlocked_percent = (calc.unlocked_fraction * U256::from(100)) / ratio_precision;
-        let initial_tokens = utils::u256_to_human(initial, decimals);
-        let locked_tokens = utils::u256_to_human(calc.locked_amount, decimals);
-        wallet_details.push((addrs.clone(), initial_tokens, locked_tokens, unlocked_percent, tge_percentage, cliff, vesting, vesting_type.clone()));
+    let mut schedules = Vec::new();
+    let mut pools = Vec::new();
+
+    for schedule in pool_data {
+        let initial = schedule.balance_at_tge;
+        let calc = calculate_schedule_vesting(schedule, ratio_precision, current_ts, options.tge_timestamp);
+        let balance = schedule_balance(schedule, &readings.schedule_balances);
+        let discrepancy = balance.and_then(|balance| ScheduleDiscrepancy::check(initial, calc.locked_amount, balance, decimals));
+        // A wallet cannot have more locked than it holds
+        let locked_amount = balance.map_or(calc.locked_amount, |balance| cmp::min(calc.locked_amount, balance));
+        if let Some(discrepancy) = &discrepancy {
+            eprintln!("Vesting wallet balance discrepancy for {:?}: {:?}", schedule.addresses, discrepancy);
+        }
+        locked_balance = locked_balance.checked_add(locked_amount).unwrap_or(locked_balance);
+        schedules.push(ScheduleBreakdown {
+            addresses: schedule.addresses.iter().map(|(addr, _)| *addr).collect(),
+            label: schedule.label.clone(),
+            vesting_type: schedule.vesting_type.clone(),
+            start_timestamp: utils::saturating_u64(schedule.start(options.tge_timestamp)),
+            tge_percentage: utils::saturating_u64(schedule.tge_percentage),
+            cliff_days: utils::saturating_u64(calc.cliff_days),
+            vesting_days: utils::saturating_u64(calc.vesting_days),
+            time_unit: calc.time_unit,
+            release_granularity: calc.granularity,
+            days_passed: utils::saturating_u64(calc.days_passed),
+            days_until_lock_ends: utils::saturating_u64(calc.days_until_lock_ends),
+            days_until_vesting_ends: utils::saturating_u64(calc.days_until_vesting_ends),
+            initial: utils::u256_to_human(initial, decimals),
+            locked: utils::u256_to_human(locked_amount, decimals),
+            unlocked_percent: utils::saturating_u64((calc.unlocked_fraction * U256::from(100)) / ratio_precision),
+            balance: balance.map(|balance| utils::u256_to_human(balance, decimals)),
+            discrepancy,
+            initial_wei: initial,
+            locked_wei: locked_amount,
+        });
     }
 
-    for (i, (initial, lock_days, vesting_days, ratio_precision, reserved)) in onchain_pool_data.iter().enumerate() {
-        let (locked_amount, unlocked_fraction) = if *reserved {
-            (*initial, U256::zero())
-        } else {
-            let calc = calculate_pool_vesting(
-                *initial,
-                U256::zero(),
-                *lock_days,
-                *vesting_days,
-                *ratio_precision,
-                current_ts,
-                tge_timestamp,
-                "linear",
-            );
-            (calc.locked_amount, calc.unlocked_fraction)
+    for input in onchain_pool_data {
+        let (locked_amount, unlocked_percent, calc) = match input.status {
+            PoolStatus::Reserved | PoolStatus::AssumedLocked => (input.initial, U256::zero(), None),
+            PoolStatus::Skipped => (U256::zero(), U256::zero(), None),
+            PoolStatus::Vesting => {
+                let schedule = Linear { tge_percentage: U256::zero(), cliff: input.lock, vesting: input.vesting };
+                let calc = calculate_vesting(
+                    input.initial,
+                    input.lock,
+                    input.vesting,
+                    input.ratio_precision,
+                    input.now,
+                    input.start,
+                    input.time_unit,
+                    options.pool_release_granularity,
+                    &schedule,
+                );
+                (calc.locked_amount, (calc.unlocked_fraction * U256::from(100)) / input.ratio_precision, Some(calc))
+            }
         };
         locked_balance = locked_balance.checked_add(locked_amount).unwrap_or(locked_balance);
-        let unlocked_percent = if *reserved { U256::zero() } else { (unlocked_fraction * U256::from(100)) / *ratio_precision };
-        let initial_tokens = utils::u256_to_human(*initial, decimals);
-        let locked_tokens = utils::u256_to_human(locked_amount, decimals);
-        pool_details.push((onchain_pool_addresses[i].0, initial_tokens, locked_tokens, unlocked_percent, *reserved, *lock_days, *vesting_days));
+
+        let cross_check = match (input.status, input.available_self_stake) {
+            (PoolStatus::Vesting, Some(available)) => {
+                let computed = input.initial.checked_sub(locked_amount).unwrap_or(U256::zero());
+                let (contract, failure) = match available {
+                    Ok(value) => (Some(value), None),
+                    Err(failure) => (None, Some(failure)),
+                };
+                let difference = contract.map(|contract| {
+                    if contract >= computed {
+                        utils::u256_to_human(contract - computed, decimals)
+                    } else {
+                        format!("-{}", utils::u256_to_human(computed - contract, decimals))
+                    }
+                });
+                if contract.is_some_and(|contract| contract != computed) {
+                    eprintln!("Pool {:?} cross-check mismatch: computed available {}, contract reports {}", input.address, computed, contract.unwrap_or_default());
+                }
+                Some(PoolCrossCheck {
+                    computed_available: utils::u256_to_human(computed, decimals),
+                    contract_available: contract.map(|value| utils::u256_to_human(value, decimals)),
+                    difference,
+                    matches: contract == Some(computed),
+                    failure,
+                })
+            }
+            _ => None,
+        };
+
+        pools.push(PoolBreakdown {
+            address: input.address,
+            status: input.status,
+            schedule_source: options.pool_vesting_mode,
+            start: utils::saturating_u64(input.start),
+            lock_days: utils::saturating_u64(units_to_days(input.lock, input.time_unit.units_per_day(), false)),
+            vesting_days: utils::saturating_u64(units_to_days(input.vesting, input.time_unit.units_per_day(), false)),
+            time_unit: calc.as_ref().map(|calc| calc.time_unit),
+            release_granularity: options.pool_release_granularity,
+            days_passed: calc.as_ref().map_or(0, |calc| utils::saturating_u64(calc.days_passed)),
+            initial: utils::u256_to_human(input.initial, decimals),
+            locked: utils::u256_to_human(locked_amount, decimals),
+            unlocked_percent: utils::saturating_u64(unlocked_percent),
+            cross_check,
+        });
+    }
+
+    let mut unbonding_incomplete = Vec::new();
+    let unbonding_balance = if options.unbonding == UnbondingTreatment::Off {
+        None
+    } else {
+        // Reserved and assumed-locked pools already count their whole balance as locked
+        let unbonding_pools: Vec<(Address, bool)> = pools
+            .iter()
+            .filter(|pool| !matches!(pool.status, PoolStatus::Reserved | PoolStatus::AssumedLocked))
+            .map(|pool| (pool.address, false))
+            .collect();
+        let unbonding_readings = staking::read_unbonding(matchain_contract.client(), &unbonding_pools, stakers, multicall, current_block.as_u64()).await?;
+        let mut unbonding = U256::zero();
+        for reading in unbonding_readings {
+            if reading.unbonding.is_none() {
+                unbonding_incomplete.push(reading.address);
+            }
+            unbonding = unbonding.saturating_add(reading.unbonding_at(U256::from(current_block.as_u64())));
+            failures.extend(reading.failures);
+        }
+        Some(unbonding)
+    };
+    let non_circulating_unbonding = match (options.unbonding, unbonding_balance) {
+        (UnbondingTreatment::Excluded, Some(unbonding)) => unbonding,
+        _ => U256::zero(),
+    };
+
+    let circulating_supply = total_supply
+        .checked_sub(excluded_balance)
+        .unwrap_or(U256::zero())
+        .checked_sub(locked_balance)
+        .unwrap_or(U256::zero())
+        .checked_sub(non_circulating_unbonding)
+        .unwrap_or(U256::zero());
+
+    let excluded: Vec<ExcludedBreakdown> = readings
+        .excluded_balances
+        .iter()
+        .map(|&(address, balance)| ExcludedBreakdown {
+            address,
+            label: excluded_matchain.iter().find(|excluded| excluded.address == address).map(|excluded| excluded.label.clone()).unwrap_or_default(),
+            balance: utils::u256_to_human(balance, decimals),
+            balance_wei: balance,
+        })
+        .collect();
+    let categories = category_breakdown(&schedules, &excluded, decimals);
+
+    let breakdown = SupplyBreakdown {
+        block: current_block.as_u64(),
+        timestamp: utils::saturating_u64(current_ts),
+        total_supply: utils::u256_to_human(total_supply, decimals),
+        excluded_balance: utils::u256_to_human(excluded_balance, decimals),
+        locked_balance: utils::u256_to_human(locked_balance, decimals),
+        unbonding_balance: unbonding_balance.map(|unbonding| utils::u256_to_human(unbonding, decimals)),
+        unbonding_treatment: options.unbonding,
+        unbonding_incomplete,
+        circulating_supply: utils::u256_to_human(circulating_supply, decimals),
+        total_supply_wei: total_supply,
+        circulating_supply_wei: circulating_supply,
+        burn_balance_wei: readings.burn_balance,
+        staked: None,
+        categories,
+        excluded,
+        schedules,
+        pools,
+        failures,
+        staking_failures: Vec::new(),
+    };
+    print_supply_overview(&breakdown);
+
+    Ok(breakdown)
+}
+
+/// MAT staked across `pool_addresses` as a share of `breakdown`'s supply figures, with the pools
+/// whose stake could not be read. Staking does not affect circulating supply, so those pools are
+/// left out rather than failing the figure.
+pub async fn get_staked_supply<M: Middleware + 'static>(
+    client: Arc<M>,
+    breakdown: &SupplyBreakdown,
+    pool_addresses: &[(Address, bool)],
+    multicall: &MulticallConfig,
+    decimals: u8,
+) -> Result<(StakedSupply, Vec<CallFailure>), anyhow::Error> {
+    let readings = staking::read_total_stake(client, pool_addresses, multicall, breakdown.block).await?;
+    let total_staked = readings.iter().fold(U256::zero(), |acc, reading| acc.saturating_add(reading.total_stake));
+    let staked = StakedSupply {
+        total_staked: utils::u256_to_human(total_staked, decimals),
+        staked_percent_of_circulating: utils::percent(total_staked, breakdown.circulating_supply_wei),
+        staked_percent_of_total: utils::percent(total_staked, breakdown.total_supply_wei),
+        pools: readings.len(),
+    };
+    eprintln!(
+        "Staked Supply: {} tokens ({}% of circulating, {}% of total) across {} pools",
+        staked.total_staked, staked.staked_percent_of_circulating, staked.staked_percent_of_total, staked.pools
+    );
+    Ok((staked, readings.into_iter().flat_map(|reading| reading.failures).collect()))
+}
+
+fn category_breakdown(schedules: &[ScheduleBreakdown], excluded: &[ExcludedBreakdown], decimals: u8) -> Vec<CategoryBreakdown> {
+    // (category, allocated, locked, excluded balance)
+    let mut totals: Vec<(&str, U256, U256, U256)> = Vec::new();
+    let amounts = schedules
+        .iter()
+        .map(|schedule| (schedule.label.category(), schedule.initial_wei, schedule.locked_wei, U256::zero()))
+        .chain(excluded.iter().map(|excluded| (excluded.label.category(), U256::zero(), U256::zero(), excluded.balance_wei)));
+    for (category, allocated, locked, excluded_balance) in amounts {
+        let index = match totals.iter().position(|total| total.0 == category) {
+            Some(index) => index,
+            None => {
+                totals.push((category, U256::zero(), U256::zero(), U256::zero()));
+                totals.len() - 1
+            }
+        };
+        let total = &mut totals[index];
+        total.1 = total.1.saturating_add(allocated);
+        total.2 = total.2.saturating_add(locked);
+        total.3 = total.3.saturating_add(excluded_balance);
     }
+    totals
+        .into_iter()
+        .map(|(category, allocated, locked, excluded_balance)| CategoryBreakdown {
+            category: category.to_string(),
+            allocated: utils::u256_to_human(allocated, decimals),
+            locked: utils::u256_to_human(locked, decimals),
+            unlocked: utils::u256_to_human(allocated.saturating_sub(locked), decimals),
+            excluded_balance: utils::u256_to_human(excluded_balance, decimals),
+            allocated_wei: allocated,
+            locked_wei: locked,
+            excluded_balance_wei: excluded_balance,
+        })
+        .collect()
+}
 
-    let total_supply_tokens = utils::u256_to_human(total_supply, decimals);
-    let excluded_balance_tokens = utils::u256_to_human(excluded_balance, decimals);
-    let locked_balance_tokens = utils::u256_to_human(locked_balance, decimals);
-    let circulating_supply_tokens = utils::u256_to_human(total_supply.checked_sub(excluded_balance).unwrap_or(U256::zero()).checked_sub(locked_balance).unwrap_or(U256::zero()), decimals);
+fn print_supply_overview(breakdown: &SupplyBreakdown) {
+    let total_supply_tokens = &breakdown.total_supply;
+    let excluded_balance_tokens = &breakdown.excluded_balance;
+    let locked_balance_tokens = &breakdown.locked_balance;
+    let circulating_supply_tokens = &breakdown.circulating_supply;
 
     // Formatted terminal output
-    eprintln!("\n=== Token Supply Overview (as of block {}) ===", current_block);
+    eprintln!("\n=== Token Supply Overview (as of block {}) ===", breakdown.block);
     eprintln!("Total Supply: {} tokens", total_supply_tokens);
     eprintln!("Excluded Balance: {} tokens", excluded_balance_tokens);
     eprintln!("Locked Balance: {} tokens", locked_balance_tokens);
+    if let Some(unbonding) = &breakdown.unbonding_balance {
+        eprintln!("Unbonding Balance: {} tokens ({:?})", unbonding, breakdown.unbonding_treatment);
+    }
     eprintln!("Circulating Supply: {} tokens", circulating_supply_tokens);
     eprintln!("\nCalculation Breakdown:");
     eprintln!("- Total Supply = Matchain Total Supply - Burned Tokens");
-    eprintln!("- Circulating Supply = Total Supply - Excluded Balance - Locked Balance");
+    if breakdown.unbonding_treatment == UnbondingTreatment::Excluded {
+        eprintln!("- Circulating Supply = Total Supply - Excluded Balance - Locked Balance - Unbonding Balance");
+    } else {
+        eprintln!("- Circulating Supply = Total Supply - Excluded Balance - Locked Balance");
+    }
     eprintln!("- Excluded Balance = Sum of balances from excluded addresses");
     eprintln!("- Locked Balance = Sum of locked tokens from vesting wallets and pools");
     eprintln!("\nWallet Vesting Details:");
     eprintln!("{:-<60}", "");
-    for (addrs, initial, locked, unlocked_percent, tge_percentage, cliff, vesting, vesting_type) in wallet_details {
-        let addrs_str = addrs.iter().map(|(addr, chain)| format!("{} ({})", addr, chain)).collect::<Vec<_>>().join(", ");
+    for schedule in &breakdown.schedules {
+        let addrs_str = schedule.addresses.iter().map(|addr| format!("{:?}", addr)).collect::<Vec<_>>().join(", ");
         eprintln!(
-            "Addresses        : {}\nInitial Balance  : {} tokens\nLocked           : {} tokens\nUnlocked         : {}%\nSchedule         : TGE = {}%, Cliff = {} days, Vesting = {} days, Type = {}\n{:-<60}",
-            addrs_str, initial, locked, unlocked_percent, tge_percentage, cliff, vesting, vesting_type, ""
+            "Addresses        : {}\nInitial Balance  : {} tokens\nWallet Balance   : {}\nLocked           : {} tokens\nUnlocked         : {}%\nSchedule         : Start = {}, TGE = {}%, Cliff = {} days, Vesting = {} days, Type = {}, Unit = {:?}, Release = {:?}\n{:-<60}",
+            addrs_str,
+            schedule.initial,
+            schedule.balance.as_ref().map_or("not read".to_string(), |balance| format!("{} tokens", balance)),
+            schedule.locked,
+            schedule.unlocked_percent,
+            schedule.start_timestamp,
+            schedule.tge_percentage,
+            schedule.cliff_days,
+            schedule.vesting_days,
+            schedule.vesting_type,
+            schedule.time_unit,
+            schedule.release_granularity,
+            ""
         );
     }
     eprintln!("\nPool Vesting Details:");
     eprintln!("{:-<60}", "");
-    for (addr, initial, locked, unlocked_percent, reserved, lock_days, vesting_days) in pool_details {
-        if reserved {
-            eprintln!(
-                "Address          : {}\nInitial Balance  : {} tokens\nLocked           : {} tokens\nUnlocked         : {}%\nSchedule         : Reserved pool (funds not subject to lock/vesting timetable)\n{:-<60}",
-                addr, initial, locked, unlocked_percent, ""
-            );
-        } else {
-            eprintln!(
-                "Address          : {}\nInitial Balance  : {} tokens\nLocked           : {} tokens\nUnlocked         : {}%\nSchedule         : Lock = {} days, Vesting = {} days\n{:-<60}",
-                addr, initial, locked, unlocked_percent, lock_days, vesting_days, ""
-            );
+    for pool in &breakdown.pools {
+        let schedule = match pool.status {
+            PoolStatus::Reserved => "Reserved pool (funds not subject to lock/vesting timetable)".to_string(),
+            PoolStatus::Skipped => "Skipped (vesting parameters could not be read)".to_string(),
+            PoolStatus::AssumedLocked => "Assumed fully locked (vesting parameters could not be read)".to_string(),
+            PoolStatus::Vesting => format!(
+                "Lock = {} days, Vesting = {} days, Source = {:?}, Start = block {}, Unit = {:?}",
+                pool.lock_days, pool.vesting_days, pool.schedule_source, pool.start, pool.time_unit
+            ),
+        };
+        eprintln!(
+            "Address          : {:?}\nInitial Balance  : {} tokens\nLocked           : {} tokens\nUnlocked         : {}%\nSchedule         : {}\n{:-<60}",
+            pool.address, pool.initial, pool.locked, pool.unlocked_percent, schedule, ""
+        );
+    }
+    if !breakdown.failures.is_empty() {
+        eprintln!("\nFailed Calls:");
+        for failure in &breakdown.failures {
+            eprintln!("- {}", failure);
         }
     }
 
@@ -310,6 +1027,4 @@ pub async fn get_circulating_supply(
         circulating_f64 / 1e6
     );
     eprintln!("=====================================\n");
-
-    Ok(circulating_supply_tokens)
-}
\ No newline at end of file
+}
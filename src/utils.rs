@@ -1,5 +1,9 @@
 // src/utils.rs
 use ethers::types::U256;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use utoipa::{IntoParams, ToSchema};
 
 pub fn u256_to_human(value: U256, decimals: u8) -> String {
     if decimals == 0 {
@@ -37,3 +41,103 @@ pub fn u256_to_human(value: U256, decimals: u8) -> String {
         format!("{}.{}", integer, fraction)
     }
 }
+
+/// Converts to `u64`, clamping values that do not fit.
+pub fn saturating_u64(value: U256) -> u64 {
+    if value > U256::from(u64::MAX) { u64::MAX } else { value.as_u64() }
+}
+
+/// Formats `part / whole` as a percentage with up to two decimals, or `0` if `whole` is zero.
+pub fn percent(part: U256, whole: U256) -> String {
+    match part.saturating_mul(U256::from(10_000u64)).checked_div(whole) {
+        Some(basis_points) => u256_to_human(basis_points, 2),
+        None => "0".to_string(),
+    }
+}
+
+/// Loads a JSON state file, starting from the default if it does not exist yet.
+pub fn load_json_state<T: DeserializeOwned + Default>(path: &Path) -> Result<T, anyhow::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+pub fn save_json_state<T: Serialize>(state: &T, path: &Path) -> Result<(), anyhow::Error> {
+    // Write then rename so a crash mid-write never leaves a truncated state file
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Unit amounts are rendered in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    /// Whole tokens, with the token's decimals applied.
+    #[default]
+    Token,
+    /// The raw integer amount.
+    Wei,
+}
+
+/// How digits beyond the requested decimal places are dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Toward zero.
+    #[default]
+    Down,
+    /// Away from zero.
+    Up,
+    /// To the nearest value, ties away from zero.
+    HalfUp,
+}
+
+// Keeps 10^places well inside U256 for any token decimals
+pub const MAX_DECIMAL_PLACES: u8 = 36;
+
+/// Output options for token amounts. The default matches [`u256_to_human`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct AmountFormat {
+    /// Whole tokens or the raw integer amount.
+    pub unit: Unit,
+    /// Fixed number of decimal places, at most 36. Unset prints every significant digit.
+    pub decimals: Option<u8>,
+    /// How digits beyond `decimals` are dropped.
+    pub rounding: Rounding,
+}
+
+impl AmountFormat {
+    pub fn format(&self, value: U256, token_decimals: u8) -> String {
+        let places = match (self.unit, self.decimals) {
+            (Unit::Wei, _) => return value.to_string(),
+            (Unit::Token, None) => return u256_to_human(value, token_decimals),
+            (Unit::Token, Some(places)) => places.min(MAX_DECIMAL_PLACES),
+        };
+
+        // Scale to an integer count of 10^-places tokens
+        let scaled = if places >= token_decimals {
+            value.saturating_mul(U256::exp10((places - token_decimals) as usize))
+        } else {
+            let divisor = U256::exp10((token_decimals - places) as usize);
+            let (quotient, remainder) = value.div_mod(divisor);
+            let round_up = match self.rounding {
+                Rounding::Down => false,
+                Rounding::Up => !remainder.is_zero(),
+                Rounding::HalfUp => remainder.saturating_mul(U256::from(2)) >= divisor,
+            };
+            if round_up { quotient.saturating_add(U256::one()) } else { quotient }
+        };
+
+        if places == 0 {
+            return scaled.to_string();
+        }
+        let (integer, fraction) = scaled.div_mod(U256::exp10(places as usize));
+        format!("{}.{:0>width$}", integer, fraction.to_string(), width = places as usize)
+    }
+}
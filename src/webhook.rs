@@ -0,0 +1,128 @@
+// src/webhook.rs
+//! Delivers notifications to webhook receivers, either as generic JSON signed with HMAC-SHA256
+//! or as Slack and Discord chat messages.
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Header carrying `sha256=<hex HMAC of the body>` on signed generic webhooks.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+// Webhook receivers are expected to answer quickly; a hung endpoint must not stall the caller
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something worth telling a webhook about.
+pub trait Notification: Serialize {
+    /// One-line, human-readable description used for chat messages.
+    fn summary(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// The notification itself as JSON, signed if a secret is set.
+    Json,
+    /// `{"text": "<summary>"}` for Slack incoming webhooks.
+    Slack,
+    /// `{"content": "<summary>"}` for Discord webhooks.
+    Discord,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "slack" => Ok(Self::Slack),
+            "discord" => Ok(Self::Discord),
+            other => Err(anyhow::anyhow!("Unknown webhook format '{}', expected json, slack or discord", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+    pub format: WebhookFormat,
+    /// HMAC key for generic JSON webhooks; chat formats are never signed.
+    pub secret: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>, format: WebhookFormat) -> Self {
+        Self { url: url.into(), format, secret: None }
+    }
+
+    pub fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.secret = secret;
+        self
+    }
+
+    /// The request body for `notification` in this webhook's format.
+    pub fn body<N: Notification>(&self, notification: &N) -> Result<Vec<u8>, anyhow::Error> {
+        let body = match self.format {
+            WebhookFormat::Json => serde_json::to_vec(notification)?,
+            WebhookFormat::Slack => serde_json::to_vec(&serde_json::json!({ "text": notification.summary() }))?,
+            WebhookFormat::Discord => serde_json::to_vec(&serde_json::json!({ "content": notification.summary() }))?,
+        };
+        Ok(body)
+    }
+
+    /// POSTs `notification`, failing on a non-2xx response.
+    pub async fn send<N: Notification>(&self, notification: &N) -> Result<(), anyhow::Error> {
+        let body = self.body(notification)?;
+        let mut request = reqwest::Client::new().post(&self.url).timeout(REQUEST_TIMEOUT).header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let (WebhookFormat::Json, Some(secret)) = (self.format, &self.secret) {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+        }
+        // Slack and Discord URLs embed their credentials, so they are kept out of errors
+        let response = request.body(body).send().await.map_err(|e| e.without_url())?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("{:?} webhook answered {}", self.format, response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// `sha256=<hex>` HMAC-SHA256 of `body` under `secret`, as sent in [`SIGNATURE_HEADER`].
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Fans notifications out to every configured webhook.
+#[derive(Debug, Clone, Default)]
+pub struct Dispatcher {
+    webhooks: Vec<Webhook>,
+}
+
+impl Dispatcher {
+    pub fn new(webhooks: Vec<Webhook>) -> Self {
+        Self { webhooks }
+    }
+
+    pub fn with(mut self, webhook: Option<Webhook>) -> Self {
+        self.webhooks.extend(webhook);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty()
+    }
+
+    /// Sends `notification` to every webhook. A failed delivery is logged and does not stop the
+    /// others. Returns the number delivered.
+    pub async fn dispatch<N: Notification>(&self, notification: &N) -> usize {
+        let mut delivered = 0;
+        for webhook in &self.webhooks {
+            match webhook.send(notification).await {
+                Ok(()) => delivered += 1,
+                Err(e) => eprintln!("Failed to deliver {:?} webhook: {:?}", webhook.format, e),
+            }
+        }
+        delivered
+    }
+}
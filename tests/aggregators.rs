@@ -0,0 +1,43 @@
+// tests/aggregators.rs
+use ethers::types::U256;
+use matchain_supply_apis::aggregators::{self, SupplyFigure, SupplyFigures};
+use serde_json::json;
+
+/// `whole.fraction` with 18 decimals, e.g. `wei("1.123456789999999999")`.
+fn wei(value: &str) -> U256 {
+    ethers::utils::parse_units(value, 18).unwrap().into()
+}
+
+fn figures(max: Option<U256>) -> SupplyFigures {
+    SupplyFigures { total: wei("1000000.5"), circulating: wei("660000.123456789999999999"), max }
+}
+
+#[test]
+fn cmc_figures_are_truncated_to_eight_decimals() {
+    assert_eq!(aggregators::cmc_figure(wei("1.123456789999999999"), 18), "1.12345678");
+    assert_eq!(aggregators::cmc_figure(wei("1.999999999"), 18), "1.99999999");
+    assert_eq!(aggregators::cmc_figure(wei("1.5"), 18), "1.5");
+    assert_eq!(aggregators::cmc_figure(wei("0.000000009"), 18), "0");
+    // Tokens with fewer decimals are printed in full
+    assert_eq!(aggregators::cmc_figure(U256::from(123_456u64), 6), "0.123456");
+
+    let supply = serde_json::to_value(aggregators::cmc_supply(&figures(None), 18)).unwrap();
+    assert_eq!(supply, json!({ "total_supply": "1000000.5", "circulating_supply": "660000.12345678", "max_supply": null }));
+}
+
+#[test]
+fn coingecko_figures_are_wrapped_in_result() {
+    let supply = serde_json::to_value(aggregators::coingecko_supply(&figures(Some(wei("1000000000"))), 18)).unwrap();
+    assert_eq!(
+        supply,
+        json!({ "result": { "total_supply": "1000000.5", "circulating_supply": "660000.123456789999999999", "max_supply": "1000000000" } })
+    );
+
+    let figures = figures(None);
+    let single = |figure: SupplyFigure| figures.get(figure).map(|value| serde_json::to_value(aggregators::coingecko_figure(value, 18)).unwrap());
+    assert_eq!(single(SupplyFigure::Total), Some(json!({ "result": "1000000.5" })));
+    assert_eq!(single(SupplyFigure::Circulating), Some(json!({ "result": "660000.123456789999999999" })));
+    assert_eq!(single(SupplyFigure::Max), None);
+    let supply = serde_json::to_value(aggregators::coingecko_supply(&figures, 18)).unwrap();
+    assert_eq!(supply["result"]["max_supply"], json!(null));
+}
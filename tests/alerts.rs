@@ -0,0 +1,281 @@
+// tests/alerts.rs
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use common::{BLOCK_TIME_MS, DAY, RpcStub, address, tokens, uint};
+use ethers::abi::Token;
+use ethers::types::{Address, U256};
+use hmac::{Hmac, Mac};
+use matchain_supply_apis::ERC20;
+use matchain_supply_apis::alerts::{self, Alert, AlertConfig, SupplyWatcher};
+use matchain_supply_apis::config::{AllocationLabel, PoolSchedule};
+use matchain_supply_apis::multicall::{CallFailure, MulticallConfig};
+use matchain_supply_apis::supply::{self, PoolFailurePolicy, PoolVestingMode, ReleaseGranularity, SupplyBreakdown, SupplyOptions, UnbondingTreatment};
+use matchain_supply_apis::webhook::{self, Dispatcher, Notification, SIGNATURE_HEADER, Webhook, WebhookFormat};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+const BLOCK: u64 = 10_000_000;
+const TGE_TIMESTAMP: u64 = 1_750_000_000;
+
+fn token() -> Address {
+    address(0x1000)
+}
+
+fn team() -> Address {
+    address(0x3000)
+}
+
+fn ecosystem() -> Address {
+    address(0x3001)
+}
+
+/// A team allocation that unlocks entirely at the end of a 30 day cliff, and an ecosystem
+/// allocation released continuously over 100 days from TGE.
+fn schedules() -> Vec<PoolSchedule> {
+    let schedule = |wallet: Address, vesting_type: &str, cliff: u64, vesting: u64, balance: u64, name: &str| PoolSchedule {
+        addresses: vec![(wallet, "Matchain".to_string())],
+        tge_percentage: U256::zero(),
+        cliff: U256::from(cliff),
+        vesting: U256::from(vesting),
+        vesting_type: vesting_type.to_string(),
+        balance_at_tge: tokens(balance),
+        release_granularity: ReleaseGranularity::Continuous,
+        tranches: Vec::new(),
+        start_timestamp: None,
+        label: AllocationLabel { name: Some(name.to_string()), category: Some(name.to_lowercase()) },
+    };
+    vec![schedule(team(), "cliff", 30, 0, 200_000, "Team"), schedule(ecosystem(), "linear", 0, 100, 100_000, "Ecosystem")]
+}
+
+/// 1,000,000 MAT minted with `burned` sent to the zero address, each vesting wallet holding its
+/// allocation.
+fn matchain(burned: u64) -> RpcStub {
+    let stub = RpcStub::new(BLOCK, TGE_TIMESTAMP + 10 * DAY);
+    stub.respond(token(), "totalSupply()", &[], &[uint(tokens(1_000_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(Address::zero())], &[uint(tokens(burned))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(team())], &[uint(tokens(200_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(ecosystem())], &[uint(tokens(100_000))]);
+    stub
+}
+
+async fn refresh(stub: &RpcStub) -> SupplyBreakdown {
+    let options = SupplyOptions {
+        tge_timestamp: U256::from(TGE_TIMESTAMP),
+        pool_failure_policy: PoolFailurePolicy::Locked,
+        pool_vesting_mode: PoolVestingMode::Replay,
+        pool_cross_check: false,
+        block_time_ms: BLOCK_TIME_MS,
+        pool_release_granularity: ReleaseGranularity::Daily,
+        unbonding: UnbondingTreatment::Off,
+    };
+    let contract = ERC20::new(token(), stub.provider());
+    supply::get_circulating_supply(&contract, &[], &schedules(), &[], &HashMap::new(), &MulticallConfig::default(), &options, 18).await.unwrap()
+}
+
+fn watcher(config: AlertConfig) -> SupplyWatcher {
+    SupplyWatcher::new(config, schedules(), U256::from(TGE_TIMESTAMP), 18)
+}
+
+#[tokio::test]
+async fn refreshes_raise_supply_unlock_and_burn_alerts() {
+    let stub = matchain(1_000);
+    let mut watcher = watcher(AlertConfig::default());
+
+    // The first refresh sets the baseline
+    assert!(watcher.observe(&refresh(&stub).await).is_empty());
+
+    // An hour of continuous ecosystem release moves supply by far less than 1%
+    stub.advance(BLOCK + 1, TGE_TIMESTAMP + 10 * DAY + 3_600);
+    assert!(watcher.observe(&refresh(&stub).await).is_empty());
+
+    // The team cliff ends
+    stub.advance(BLOCK + 2, TGE_TIMESTAMP + 31 * DAY);
+    let alerts = watcher.observe(&refresh(&stub).await);
+    assert_eq!(alerts.len(), 2, "unexpected alerts: {:?}", alerts);
+    let Alert::CirculatingSupplyChange { previous, current, change_percent, .. } = &alerts[0] else {
+        panic!("expected a supply change, got {:?}", alerts[0]);
+    };
+    assert_eq!((previous.as_str(), current.as_str(), change_percent.as_str()), ("709041.6", "930000", "31.16"));
+    assert_eq!(
+        alerts[1],
+        Alert::Unlock {
+            block: BLOCK + 2,
+            timestamp: TGE_TIMESTAMP + 31 * DAY,
+            label: AllocationLabel { name: Some("Team".to_string()), category: Some("team".to_string()) },
+            addresses: vec![team()],
+            unlocked: "200000".to_string(),
+            locked: "0".to_string(),
+        }
+    );
+    assert_eq!(alerts[1].summary(), format!("Team (team) unlocked 200000 MAT at block {}, 0 MAT still locked", BLOCK + 2));
+
+    // Burning 5,000 moves supply by about 0.5%
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(Address::zero())], &[uint(tokens(6_000))]);
+    stub.advance(BLOCK + 3, TGE_TIMESTAMP + 31 * DAY + 1);
+    let alerts = watcher.observe(&refresh(&stub).await);
+    assert_eq!(
+        alerts,
+        [Alert::BurnBalanceChange { block: BLOCK + 3, timestamp: TGE_TIMESTAMP + 31 * DAY + 1, previous: "1000".to_string(), current: "6000".to_string() }]
+    );
+
+    // With a 0.1% threshold a further 5,000 burned also counts as a supply drop
+    let mut sensitive = watcher_at(&stub, AlertConfig { supply_change_bps: 10, ..AlertConfig::default() }).await;
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(Address::zero())], &[uint(tokens(11_000))]);
+    stub.advance(BLOCK + 4, TGE_TIMESTAMP + 31 * DAY + 2);
+    let alerts = sensitive.observe(&refresh(&stub).await);
+    assert_eq!(alerts.len(), 2, "unexpected alerts: {:?}", alerts);
+    assert!(alerts[0].summary().starts_with("Circulating supply fell 0.54% from 925000 to 920000 MAT at block "), "{}", alerts[0].summary());
+}
+
+async fn watcher_at(stub: &RpcStub, config: AlertConfig) -> SupplyWatcher {
+    let mut watcher = watcher(config);
+    watcher.observe(&refresh(stub).await);
+    watcher
+}
+
+#[tokio::test]
+async fn repeated_read_failures_alert_once_per_streak() {
+    let stub = matchain(1_000);
+    let mut watcher = watcher(AlertConfig { failure_threshold: 2, ..AlertConfig::default() });
+    let error = anyhow::anyhow!("connection refused");
+
+    assert!(watcher.failed(&error).is_empty());
+    assert_eq!(watcher.failed(&error), [Alert::ReadFailures { consecutive_failures: 2, error: "connection refused".to_string() }]);
+    assert!(watcher.failed(&error).is_empty());
+
+    // A clean refresh ends the streak
+    assert!(watcher.observe(&refresh(&stub).await).is_empty());
+    assert!(watcher.failed(&error).is_empty());
+    assert_eq!(watcher.failed(&error).len(), 1);
+}
+
+#[tokio::test]
+async fn only_supply_read_failures_count_towards_the_streak() {
+    let stub = matchain(1_000);
+    let mut watcher = watcher(AlertConfig { failure_threshold: 1, ..AlertConfig::default() });
+    let pool = address(0x4000);
+    let failure = CallFailure { target: pool, function: "totalStake".to_string(), reason: "reverted".to_string() };
+
+    let mut breakdown = refresh(&stub).await;
+    breakdown.staking_failures.push(failure.clone());
+    assert!(watcher.observe(&breakdown).is_empty());
+
+    breakdown.failures.push(failure);
+    assert_eq!(watcher.observe(&breakdown), [Alert::ReadFailures { consecutive_failures: 1, error: format!("totalStake on {:?} failed: reverted", pool) }]);
+}
+
+#[tokio::test]
+async fn continuous_linear_release_only_alerts_for_the_tge_share() {
+    let stub = matchain(1_000);
+    // Vesting starts at the end of a 20 day cliff with nothing at TGE, and a partner allocation
+    // starting on day 20 releases 10% then vests after its own 20 day cliff
+    let ecosystem = schedules().remove(1);
+    let advisors = PoolSchedule {
+        addresses: vec![(address(0x3002), "Matchain".to_string())],
+        cliff: U256::from(20),
+        balance_at_tge: tokens(10_000),
+        label: AllocationLabel { name: Some("Advisors".to_string()), category: Some("advisors".to_string()) },
+        ..ecosystem
+    };
+    let partners = PoolSchedule {
+        addresses: vec![(address(0x3003), "Matchain".to_string())],
+        tge_percentage: U256::from(10),
+        start_timestamp: Some(U256::from(TGE_TIMESTAMP + 20 * DAY)),
+        label: AllocationLabel { name: Some("Partners".to_string()), category: Some("partners".to_string()) },
+        ..advisors.clone()
+    };
+    let schedules = vec![advisors, partners];
+    let mut watcher = SupplyWatcher::new(AlertConfig { supply_change_bps: 10_000, ..AlertConfig::default() }, schedules, U256::from(TGE_TIMESTAMP), 18);
+    stub.advance(BLOCK + 1, TGE_TIMESTAMP + 19 * DAY);
+    assert!(watcher.observe(&refresh(&stub).await).is_empty());
+
+    stub.advance(BLOCK + 2, TGE_TIMESTAMP + 21 * DAY);
+    let alerts = watcher.observe(&refresh(&stub).await);
+    assert_eq!(alerts.len(), 1, "unexpected alerts: {:?}", alerts);
+    let Alert::Unlock { label, unlocked, locked, .. } = &alerts[0] else {
+        panic!("expected an unlock, got {:?}", alerts[0]);
+    };
+    assert_eq!((label.name.as_deref(), unlocked.as_str(), locked.as_str()), (Some("Partners"), "1000", "9000"));
+
+    // The partner cliff ends
+    stub.advance(BLOCK + 3, TGE_TIMESTAMP + 41 * DAY);
+    assert!(watcher.observe(&refresh(&stub).await).is_empty());
+}
+
+#[test]
+fn supply_change_thresholds_parse_as_percentages() {
+    assert_eq!(alerts::parse_basis_points("1").unwrap(), 100);
+    assert_eq!(alerts::parse_basis_points("0.25").unwrap(), 25);
+    assert!(alerts::parse_basis_points("-1").is_err());
+    assert!(alerts::parse_basis_points("lots").is_err());
+}
+
+type Received = Arc<Mutex<Vec<(String, HeaderMap, Bytes)>>>;
+
+/// A local webhook receiver that records every request and answers 500 on `/broken`.
+async fn receiver() -> (String, Received) {
+    async fn record(State(received): State<Received>, uri: axum::http::Uri, headers: HeaderMap, body: Bytes) -> StatusCode {
+        let path = uri.path().to_string();
+        let status = if path == "/broken" { StatusCode::INTERNAL_SERVER_ERROR } else { StatusCode::OK };
+        received.lock().unwrap().push((path, headers, body));
+        status
+    }
+
+    let received = Received::default();
+    let app = axum::Router::new().route("/{hook}", post(record)).with_state(received.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, received)
+}
+
+#[tokio::test]
+async fn dispatcher_posts_signed_json_and_chat_messages() {
+    let (url, received) = receiver().await;
+    let dispatcher = Dispatcher::new(vec![
+        Webhook::new(format!("{}/json", url), WebhookFormat::Json).with_secret(Some("s3cret".to_string())),
+        Webhook::new(format!("{}/unsigned", url), WebhookFormat::Json),
+        Webhook::new(format!("{}/slack", url), WebhookFormat::Slack),
+        Webhook::new(format!("{}/discord", url), WebhookFormat::Discord),
+        Webhook::new(format!("{}/broken", url), WebhookFormat::Json),
+    ]);
+    let alert = Alert::BurnBalanceChange { block: BLOCK, timestamp: TGE_TIMESTAMP, previous: "1000".to_string(), current: "6000".to_string() };
+
+    // The broken receiver does not stop delivery to the others
+    assert_eq!(dispatcher.dispatch(&alert).await, 4);
+
+    let received = received.lock().unwrap();
+    let paths: Vec<&str> = received.iter().map(|(path, _, _)| path.as_str()).collect();
+    assert_eq!(paths, ["/json", "/unsigned", "/slack", "/discord", "/broken"]);
+
+    let (_, headers, body) = &received[0];
+    let json: serde_json::Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(json, serde_json::json!({ "event": "burn_balance_change", "block": BLOCK, "timestamp": TGE_TIMESTAMP, "previous": "1000", "current": "6000" }));
+    assert_eq!(headers["content-type"], "application/json");
+    let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+    assert_eq!(signature, webhook::sign("s3cret", body));
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+    mac.update(body);
+    mac.verify_slice(&hex::decode(signature.strip_prefix("sha256=").unwrap()).unwrap()).unwrap();
+
+    assert!(!received[1].1.contains_key(SIGNATURE_HEADER));
+    let summary = format!("Burn balance changed from 1000 to 6000 MAT at block {}", BLOCK);
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&received[2].2).unwrap(), serde_json::json!({ "text": summary }));
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&received[3].2).unwrap(), serde_json::json!({ "content": summary }));
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_errors() {
+    let (url, _) = receiver().await;
+    let alert = Alert::ReadFailures { consecutive_failures: 3, error: "timeout".to_string() };
+
+    let error = Webhook::new(format!("{}/broken", url), WebhookFormat::Slack).send(&alert).await.unwrap_err();
+    assert_eq!(error.to_string(), "Slack webhook answered 500 Internal Server Error");
+    assert!(Webhook::new(format!("{}/ok", url), WebhookFormat::Slack).send(&alert).await.is_ok());
+}
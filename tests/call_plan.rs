@@ -0,0 +1,42 @@
+// tests/call_plan.rs
+mod common;
+
+use common::{address, tokens, uint};
+use ethers::abi::Token;
+use matchain_supply_apis::call_plan::{DecodeError, PlannedRead, SupplyCallPlan};
+use matchain_supply_apis::multicall::CallFailure;
+use matchain_supply_apis::supply::PoolVestingMode;
+
+fn vesting_pool() -> ethers::types::Address {
+    address(0x4000)
+}
+
+/// Results for a contract-mode plan of the vesting pool, with `schedule` as getVestingSchedule's result.
+fn results(schedule: Token) -> Vec<Result<Token, CallFailure>> {
+    [uint(tokens(1_000_000)), uint(tokens(1_000)), uint(tokens(10_000)), schedule, uint(1u64), uint(172_800u64), uint(1_000_000u64)].into_iter().map(Ok).collect()
+}
+
+#[test]
+fn unexpected_pool_values_are_pool_failures() {
+    let plan = SupplyCallPlan::new(&[], &[], &[(vesting_pool(), false)], PoolVestingMode::Contract, false);
+    assert_eq!(plan.reads()[3], PlannedRead::PoolVestingSchedule(vesting_pool()));
+
+    let readings = plan.decode(results(Token::Tuple(vec![uint(5u64), uint(50u64)]))).unwrap();
+    assert_eq!((readings.pools[0].lock_days.as_u64(), readings.pools[0].vesting_days.as_u64()), (5, 50));
+    assert!(readings.pools[0].failures.is_empty());
+
+    // One value where two are expected
+    let readings = plan.decode(results(uint(5u64))).unwrap();
+    let failures: Vec<(_, &str, &str)> = readings.pools[0].failures.iter().map(|failure| (failure.target, failure.function.as_str(), failure.reason.as_str())).collect();
+    assert_eq!(failures, [(vesting_pool(), "getVestingSchedule", "unexpected return value")]);
+    assert_eq!(readings.total_supply, tokens(1_000_000));
+}
+
+#[test]
+fn unexpected_token_values_fail_decoding() {
+    let plan = SupplyCallPlan::new(&[], &[], &[(vesting_pool(), false)], PoolVestingMode::Contract, false);
+    let mut results = results(Token::Tuple(vec![uint(5u64), uint(50u64)]));
+    results[0] = Ok(Token::Bool(true));
+
+    assert!(matches!(plan.decode(results), Err(DecodeError::UnexpectedType { read: PlannedRead::TotalSupply, .. })));
+}
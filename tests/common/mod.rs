@@ -0,0 +1,255 @@
+// tests/common/mod.rs
+//! In-process JSON-RPC stub for running the supply pipeline offline. `eth_call`s are answered
+//! from canned responses keyed by target and calldata, and Multicall3 `aggregate3` batches are
+//! unpacked against the same responses, so multicall and direct-call paths see identical data.
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use ethers::abi::{self, ParamType, Token};
+use ethers::contract::MULTICALL_ADDRESS;
+use ethers::providers::{JsonRpcClient, JsonRpcError, MockError, Provider};
+use ethers::types::{Address, Bytes, H256, Log, U256};
+use ethers::utils::id;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+pub const BLOCK_TIME_MS: u64 = 500;
+pub const BLOCKS_PER_DAY: u64 = 86_400_000 / BLOCK_TIME_MS;
+pub const DAY: u64 = 86_400;
+
+/// `n` whole tokens with 18 decimals.
+pub fn tokens(n: u64) -> U256 {
+    U256::from(n) * U256::exp10(18)
+}
+
+pub fn uint(value: impl Into<U256>) -> Token {
+    Token::Uint(value.into())
+}
+
+pub fn address(n: u64) -> Address {
+    Address::from_low_u64_be(n)
+}
+
+#[derive(Debug, Clone)]
+enum Response {
+    Return(Vec<u8>),
+    Revert,
+}
+
+#[derive(Debug, Default)]
+struct StubState {
+    block_number: u64,
+    timestamp: u64,
+    code: HashMap<Address, Bytes>,
+    responses: HashMap<(Address, Vec<u8>), Response>,
+    logs: Vec<Log>,
+    eth_calls: usize,
+    aggregate3_calls: usize,
+    /// Drops `aggregate3` results past this many, like a misbehaving aggregator.
+    multicall_limit: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcStub {
+    state: Arc<Mutex<StubState>>,
+}
+
+impl RpcStub {
+    /// A chain at `block_number`/`timestamp` with Multicall3 deployed at its canonical address.
+    pub fn new(block_number: u64, timestamp: u64) -> Self {
+        let state = StubState {
+            block_number,
+            timestamp,
+            code: HashMap::from([(MULTICALL_ADDRESS, Bytes::from(vec![0x60, 0x80]))]),
+            ..Default::default()
+        };
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+
+    pub fn without_multicall(self) -> Self {
+        self.state.lock().unwrap().code.remove(&MULTICALL_ADDRESS);
+        self
+    }
+
+    /// Deploys `code` at `address`, as returned by `eth_getCode`.
+    pub fn code(&self, address: Address, code: &[u8]) -> &Self {
+        self.state.lock().unwrap().code.insert(address, Bytes::from(code.to_vec()));
+        self
+    }
+
+    /// Makes `aggregate3` return at most `limit` results.
+    pub fn truncate_multicall(&self, limit: usize) -> &Self {
+        self.state.lock().unwrap().multicall_limit = Some(limit);
+        self
+    }
+
+    pub fn provider(&self) -> Arc<Provider<RpcStub>> {
+        Arc::new(Provider::new(self.clone()))
+    }
+
+    /// Makes `target.signature(args)` return `output`.
+    pub fn respond(&self, target: Address, signature: &str, args: &[Token], output: &[Token]) -> &Self {
+        self.state.lock().unwrap().responses.insert((target, calldata(signature, args)), Response::Return(abi::encode(output)));
+        self
+    }
+
+    /// Makes `target.signature(args)` revert without data.
+    pub fn revert(&self, target: Address, signature: &str, args: &[Token]) -> &Self {
+        self.state.lock().unwrap().responses.insert((target, calldata(signature, args)), Response::Revert);
+        self
+    }
+
+    /// Moves the head to `block_number` at `timestamp`.
+    pub fn advance(&self, block_number: u64, timestamp: u64) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        state.block_number = block_number;
+        state.timestamp = timestamp;
+        self
+    }
+
+    /// Adds a log served by `eth_getLogs` when it matches the filter's address, topics and range.
+    pub fn log(&self, address: Address, topics: Vec<H256>, data: Vec<u8>, block_number: u64, transaction_hash: H256) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let log_index = state.logs.len();
+        state.logs.push(Log {
+            address,
+            topics,
+            data: Bytes::from(data),
+            block_number: Some(block_number.into()),
+            transaction_hash: Some(transaction_hash),
+            log_index: Some(log_index.into()),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Total `eth_call` requests received, including `aggregate3` batches.
+    pub fn eth_calls(&self) -> usize {
+        self.state.lock().unwrap().eth_calls
+    }
+
+    pub fn aggregate3_calls(&self) -> usize {
+        self.state.lock().unwrap().aggregate3_calls
+    }
+
+    fn call(&self, tx: &Value) -> Result<Value, MockError> {
+        let target: Address = serde_json::from_value(tx["to"].clone())?;
+        let data: Bytes = serde_json::from_value(if tx["data"].is_null() { tx["input"].clone() } else { tx["data"].clone() })?;
+
+        let mut state = self.state.lock().unwrap();
+        state.eth_calls += 1;
+        if target == MULTICALL_ADDRESS && state.code.contains_key(&MULTICALL_ADDRESS) && data.starts_with(&id("aggregate3((address,bool,bytes)[])")) {
+            state.aggregate3_calls += 1;
+            return state.aggregate3(&data[4..]).map(|output| json!(Bytes::from(output)));
+        }
+        match state.responses.get(&(target, data.to_vec())) {
+            Some(Response::Return(output)) => Ok(json!(Bytes::from(output.clone()))),
+            _ => Err(reverted("execution reverted")),
+        }
+    }
+}
+
+impl StubState {
+    fn aggregate3(&self, input: &[u8]) -> Result<Vec<u8>, MockError> {
+        let call_type = ParamType::Array(Box::new(ParamType::Tuple(vec![ParamType::Address, ParamType::Bool, ParamType::Bytes])));
+        let decoded = abi::decode(&[call_type], input).map_err(|e| reverted(&e.to_string()))?;
+        let calls = decoded.into_iter().next().and_then(Token::into_array).unwrap_or_default();
+
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            let mut fields = call.into_tuple().unwrap_or_default().into_iter();
+            let (Some(Token::Address(target)), Some(Token::Bool(allow_failure)), Some(Token::Bytes(data))) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(reverted("malformed aggregate3 call"));
+            };
+            match self.responses.get(&(target, data)) {
+                Some(Response::Return(output)) => results.push(Token::Tuple(vec![Token::Bool(true), Token::Bytes(output.clone())])),
+                _ if allow_failure => results.push(Token::Tuple(vec![Token::Bool(false), Token::Bytes(Vec::new())])),
+                _ => return Err(reverted("execution reverted: Multicall3: call failed")),
+            }
+        }
+        results.truncate(self.multicall_limit.unwrap_or(results.len()));
+        Ok(abi::encode(&[Token::Array(results)]))
+    }
+
+    fn logs(&self, filter: &Value) -> Vec<Log> {
+        let block = |key: &str| filter[key].as_str().and_then(|value| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok());
+        let (from, to) = (block("fromBlock").unwrap_or(0), block("toBlock").unwrap_or(self.block_number));
+        // Filter fields are either a single value, a list of alternatives, or null for any
+        let matches = |expected: &Value, actual: String| match expected {
+            Value::Null => true,
+            Value::Array(values) => values.iter().any(|value| value.as_str().is_some_and(|value| value.eq_ignore_ascii_case(&actual))),
+            value => value.as_str().is_some_and(|value| value.eq_ignore_ascii_case(&actual)),
+        };
+        self.logs
+            .iter()
+            .filter(|log| (from..=to).contains(&log.block_number.unwrap_or_default().as_u64()))
+            .filter(|log| matches(&filter["address"], format!("{:?}", log.address)))
+            .filter(|log| {
+                let topics = filter["topics"].as_array().cloned().unwrap_or_default();
+                topics.iter().enumerate().all(|(i, expected)| expected.is_null() || log.topics.get(i).is_some_and(|topic| matches(expected, format!("{:?}", topic))))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn block(&self) -> Value {
+        json!({
+            "number": format!("{:#x}", self.block_number),
+            "hash": format!("{:#066x}", self.block_number),
+            "parentHash": format!("{:#066x}", self.block_number.saturating_sub(1)),
+            "sha3Uncles": format!("{:#066x}", 0),
+            "miner": format!("{:#042x}", 0),
+            "stateRoot": format!("{:#066x}", 0),
+            "transactionsRoot": format!("{:#066x}", 0),
+            "receiptsRoot": format!("{:#066x}", 0),
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "difficulty": "0x0",
+            "gasUsed": "0x0",
+            "gasLimit": "0x1c9c380",
+            "extraData": "0x",
+            "timestamp": format!("{:#x}", self.timestamp),
+            "transactions": [],
+            "uncles": [],
+        })
+    }
+}
+
+fn calldata(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data
+}
+
+fn reverted(message: &str) -> MockError {
+    MockError::JsonRpcError(JsonRpcError { code: 3, message: message.to_string(), data: None })
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcStub {
+    type Error = MockError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params)?;
+        let response = match method {
+            "eth_call" => self.call(&params[0])?,
+            "eth_blockNumber" => json!(format!("{:#x}", self.state.lock().unwrap().block_number)),
+            "eth_getBlockByNumber" => self.state.lock().unwrap().block(),
+            "eth_getCode" => {
+                let target: Address = serde_json::from_value(params[0].clone())?;
+                json!(self.state.lock().unwrap().code.get(&target).cloned().unwrap_or_default())
+            }
+            "eth_chainId" => json!("0x2b6"),
+            "eth_getLogs" => json!(self.state.lock().unwrap().logs(&params[0])),
+            method => return Err(MockError::JsonRpcError(JsonRpcError { code: -32601, message: format!("{} is not stubbed", method), data: None })),
+        };
+        Ok(serde_json::from_value(response)?)
+    }
+}
@@ -0,0 +1,122 @@
+// tests/discovery.rs
+mod common;
+
+use common::{RpcStub, address};
+use ethers::abi::{self, Token};
+use ethers::types::{Address, H256};
+use ethers::utils::keccak256;
+use matchain_supply_apis::config::{AllocationLabel, ExcludedAddress};
+use matchain_supply_apis::discovery::{self, DEFAULT_CREATION_EVENT, DiscoveredPool, DiscoveredPools, DiscoveryConfig, DiscoverySource};
+
+const BLOCK: u64 = 10_000_000;
+const TIMESTAMP: u64 = 1_750_000_000;
+const POOL_CODE: &[u8] = &[0x60, 0x80, 0x60, 0x40];
+
+fn factory() -> Address {
+    address(0x8000)
+}
+
+fn config(name: &str) -> DiscoveryConfig {
+    DiscoveryConfig {
+        factory: Some(factory()),
+        creation_event: DEFAULT_CREATION_EVENT.to_string(),
+        code_hash: None,
+        from_block: BLOCK - 10,
+        block_range: 4,
+        confirmations: 0,
+        state_path: std::env::temp_dir().join(format!("discovered_pools_{}_{}.json", name, std::process::id())),
+    }
+}
+
+/// `PoolCreated(address indexed pool)`.
+fn created(stub: &RpcStub, pool: Address, block: u64) {
+    let topics = vec![H256(keccak256(DEFAULT_CREATION_EVENT)), H256::from(pool)];
+    stub.log(factory(), topics, Vec::new(), block, H256::from_low_u64_be(block));
+}
+
+fn addresses(state: &DiscoveredPools) -> Vec<Address> {
+    state.pools.iter().map(|pool| pool.address).collect()
+}
+
+#[tokio::test]
+async fn factory_events_are_scanned_and_resumed() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let client = stub.provider();
+    let config = config("factory");
+    let mut state = DiscoveredPools::default();
+    created(&stub, address(0x4000), BLOCK - 8);
+    created(&stub, address(0x4001), BLOCK - 1);
+    // Another event from the factory is not a creation
+    stub.log(factory(), vec![H256(keccak256("Upgraded(address)")), H256::from(address(0x4009))], Vec::new(), BLOCK - 5, H256::zero());
+
+    assert_eq!(discovery::discover(client.as_ref(), &config, &mut state).await.unwrap(), 2);
+    assert_eq!(addresses(&state), [address(0x4000), address(0x4001)]);
+    assert_eq!((state.pools[0].block, state.pools[0].source), (BLOCK - 8, DiscoverySource::FactoryEvent));
+    assert_eq!(state.last_scanned_block, Some(BLOCK));
+
+    // The next scan starts after the last scanned block, so an event inside it is not read again
+    created(&stub, address(0x4002), BLOCK - 3);
+    created(&stub, address(0x4003), BLOCK + 4);
+    stub.advance(BLOCK + 5, TIMESTAMP + 5);
+    let mut resumed = DiscoveredPools::load(&config.state_path).unwrap();
+    assert_eq!(discovery::discover(client.as_ref(), &config, &mut resumed).await.unwrap(), 1);
+    assert_eq!(addresses(&resumed), [address(0x4000), address(0x4001), address(0x4003)]);
+    assert_eq!(resumed.last_scanned_block, Some(BLOCK + 5));
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn scans_stay_behind_the_head_by_the_confirmation_depth() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let config = DiscoveryConfig { confirmations: 3, ..config("confirmed") };
+    let mut state = DiscoveredPools::default();
+    created(&stub, address(0x4000), BLOCK - 5);
+    created(&stub, address(0x4001), BLOCK - 1);
+
+    assert_eq!(discovery::discover(stub.provider().as_ref(), &config, &mut state).await.unwrap(), 1);
+    assert_eq!(state.last_scanned_block, Some(BLOCK - 3));
+
+    // Once confirmed the newer pool is picked up
+    stub.advance(BLOCK + 2, TIMESTAMP + 2);
+    assert_eq!(discovery::discover(stub.provider().as_ref(), &config, &mut state).await.unwrap(), 1);
+    assert_eq!(addresses(&state), [address(0x4000), address(0x4001)]);
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn unindexed_creation_events_use_the_first_data_word() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let config = DiscoveryConfig { creation_event: "PoolDeployed(address,uint256)".to_string(), ..config("data") };
+    let data = abi::encode(&[Token::Address(address(0x4000)), Token::Uint(7u64.into())]);
+    stub.log(factory(), vec![H256(keccak256("PoolDeployed(address,uint256)"))], data, BLOCK - 2, H256::zero());
+
+    let mut state = DiscoveredPools::default();
+    discovery::discover(stub.provider().as_ref(), &config, &mut state).await.unwrap();
+    assert_eq!(addresses(&state), [address(0x4000)]);
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn factory_candidates_must_match_the_code_hash() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let config = DiscoveryConfig { code_hash: Some(H256(keccak256(POOL_CODE))), ..config("code_hash") };
+    stub.code(address(0x4000), POOL_CODE).code(address(0x4001), &[0x00]);
+    created(&stub, address(0x4000), BLOCK - 6);
+    created(&stub, address(0x4001), BLOCK - 4);
+    created(&stub, address(0x4002), BLOCK - 2);
+
+    let mut state = DiscoveredPools::default();
+    assert_eq!(discovery::discover(stub.provider().as_ref(), &config, &mut state).await.unwrap(), 1);
+    assert_eq!(addresses(&state), [address(0x4000)]);
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[test]
+fn merged_pools_keep_manual_flags_and_skip_excluded_addresses() {
+    let discovered = |address: Address| DiscoveredPool { address, block: BLOCK, source: DiscoverySource::FactoryEvent };
+    let state = DiscoveredPools { last_scanned_block: Some(BLOCK), pools: vec![discovered(address(0x5000)), discovered(address(0x4001)), discovered(address(0x2000))] };
+    let excluded = [ExcludedAddress { address: address(0x2000), chain: "Matchain".to_string(), label: AllocationLabel { name: Some("Treasury".to_string()), category: Some("treasury".to_string()) } }];
+
+    let pools = discovery::merge_pools(&[(address(0x4000), false), (address(0x5000), true)], &state, &excluded);
+    assert_eq!(pools, [(address(0x4000), false), (address(0x5000), true), (address(0x4001), false)]);
+}
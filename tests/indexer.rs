@@ -0,0 +1,109 @@
+// tests/indexer.rs
+mod common;
+
+use common::{RpcStub, address, tokens, uint};
+use ethers::abi::{self, Token};
+use ethers::types::{Address, H256};
+use ethers::utils::keccak256;
+use matchain_supply_apis::indexer::{self, EventStore, IndexerConfig};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+const BLOCK: u64 = 10_000_000;
+const TIMESTAMP: u64 = 1_750_000_000;
+const CONFIRMATIONS: u64 = 5;
+
+fn pool() -> Address {
+    address(0x4000)
+}
+
+fn owner() -> Address {
+    address(0x6000)
+}
+
+fn delegator() -> Address {
+    address(0x6001)
+}
+
+fn other_pool() -> Address {
+    address(0x4001)
+}
+
+fn config() -> IndexerConfig {
+    IndexerConfig { from_block: BLOCK - 100, block_range: 30, confirmations: CONFIRMATIONS }
+}
+
+fn events_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("staking_events_{}_{}.jsonl", name, std::process::id()))
+}
+
+fn remove(path: &PathBuf) {
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(path.with_extension("cursors.json")).unwrap();
+}
+
+/// `Staked(address staker, uint256 amount)` or `Unstaked(...)`, neither argument indexed.
+fn stake_event(stub: &RpcStub, event: &str, pool: Address, staker: Address, amount: u64, block: u64) {
+    let data = abi::encode(&[Token::Address(staker), uint(tokens(amount))]);
+    stub.log(pool, vec![H256(keccak256(format!("{}(address,uint256)", event)))], data, block, H256::from_low_u64_be(block));
+}
+
+fn blocks(store: &RwLock<EventStore>, pool: Address) -> Vec<(u64, &'static str)> {
+    store.read().unwrap().pool_history(pool, None, None, 18).events.iter().map(|event| (event.block, event.event)).collect()
+}
+
+#[tokio::test]
+async fn new_pools_are_backfilled_then_followed() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let client = stub.provider();
+    let path = events_path("backfill");
+    let store = RwLock::new(EventStore::open(&path).unwrap());
+    stake_event(&stub, "Staked", pool(), owner(), 10_000, BLOCK - 90);
+    stake_event(&stub, "Staked", other_pool(), delegator(), 2_000, BLOCK - 50);
+    stake_event(&stub, "Unstaked", pool(), owner(), 1_000, BLOCK - 10);
+    // Not confirmed until the head passes BLOCK + 2
+    stake_event(&stub, "Staked", pool(), delegator(), 500, BLOCK - 3);
+
+    assert_eq!(indexer::index(client.as_ref(), &config(), &store, &[pool()]).await.unwrap(), 2);
+    assert_eq!(blocks(&store, pool()), [(BLOCK - 90, "staked"), (BLOCK - 10, "unstaked")]);
+    assert_eq!(store.read().unwrap().indexed_to(pool()), Some(BLOCK - CONFIRMATIONS));
+
+    // A pool added later is backfilled on its own, then both follow the head
+    assert_eq!(indexer::index(client.as_ref(), &config(), &store, &[pool(), other_pool()]).await.unwrap(), 1);
+    assert_eq!(store.read().unwrap().indexed_to(other_pool()), Some(BLOCK - CONFIRMATIONS));
+    stub.advance(BLOCK + 10, TIMESTAMP + 5);
+    assert_eq!(indexer::index(client.as_ref(), &config(), &store, &[pool(), other_pool()]).await.unwrap(), 1);
+    assert_eq!(blocks(&store, pool()).last(), Some(&(BLOCK - 3, "staked")));
+
+    let stakers = store.read().unwrap().stakers();
+    assert_eq!(stakers[&pool()], [owner(), delegator()]);
+    assert_eq!(stakers[&other_pool()], [delegator()]);
+    remove(&path);
+}
+
+#[tokio::test]
+async fn stores_reopen_without_torn_or_unconfirmed_lines() {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    let path = events_path("reopen");
+    let store = RwLock::new(EventStore::open(&path).unwrap());
+    stake_event(&stub, "Staked", pool(), owner(), 10_000, BLOCK - 90);
+    stake_event(&stub, "Unstaked", pool(), owner(), 1_000, BLOCK - 10);
+    indexer::index(stub.provider().as_ref(), &config(), &store, &[pool()]).await.unwrap();
+    let indexed = blocks(&store, pool());
+
+    let reopened = RwLock::new(EventStore::open(&path).unwrap());
+    assert_eq!(blocks(&reopened, pool()), indexed);
+    assert_eq!(reopened.read().unwrap().indexed_to(pool()), Some(BLOCK - CONFIRMATIONS));
+
+    // A crash after appending an event past the cursor, then mid-way through the next line
+    let lines = std::fs::read_to_string(&path).unwrap();
+    let past_cursor = lines.lines().last().unwrap().replace(&format!("\"block\":{}", BLOCK - 10), &format!("\"block\":{}", BLOCK - 1));
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    write!(file, "{}\n{}", past_cursor, &past_cursor[..20]).unwrap();
+
+    let reopened = RwLock::new(EventStore::open(&path).unwrap());
+    assert_eq!(blocks(&reopened, pool()), indexed);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), lines);
+    remove(&path);
+}
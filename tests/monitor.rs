@@ -0,0 +1,182 @@
+// tests/monitor.rs
+mod common;
+
+use common::{DAY, RpcStub, address, tokens, uint};
+use ethers::abi::{self, Token};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::keccak256;
+use matchain_supply_apis::ERC20;
+use matchain_supply_apis::config::{AllocationLabel, PoolSchedule};
+use matchain_supply_apis::monitor::{self, MonitorConfig, MonitorState, TRANSFER_EVENT};
+use matchain_supply_apis::multicall::MulticallConfig;
+use matchain_supply_apis::supply::ReleaseGranularity;
+
+const BLOCK: u64 = 10_000_000;
+const TGE_TIMESTAMP: u64 = 1_750_000_000;
+
+fn token() -> Address {
+    address(0x1000)
+}
+
+fn wallet() -> Address {
+    address(0x3000)
+}
+
+fn recipient() -> Address {
+    address(0x9000)
+}
+
+/// 200,000 MAT with 10% at TGE and a 30 day cliff, so 180,000 is locked for the first month.
+fn schedules() -> Vec<PoolSchedule> {
+    vec![PoolSchedule {
+        addresses: vec![(wallet(), "Matchain".to_string())],
+        tge_percentage: U256::from(10),
+        cliff: U256::from(30),
+        vesting: U256::from(300),
+        vesting_type: "linear".to_string(),
+        balance_at_tge: tokens(200_000),
+        release_granularity: ReleaseGranularity::Continuous,
+        tranches: Vec::new(),
+        start_timestamp: None,
+        label: AllocationLabel { name: Some("Team".to_string()), category: Some("team".to_string()) },
+    }]
+}
+
+fn holding(stub: &RpcStub, amount: U256) {
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(wallet())], &[uint(amount)]);
+}
+
+fn transfer(stub: &RpcStub, from: Address, to: Address, amount: U256, block: u64) {
+    let topics = vec![H256(keccak256(TRANSFER_EVENT)), H256::from(from), H256::from(to)];
+    stub.log(token(), topics, abi::encode(&[uint(amount)]), block, H256::from_low_u64_be(block));
+}
+
+fn config(name: &str) -> MonitorConfig {
+    let state_path = std::env::temp_dir().join(format!("outflow_monitor_{}_{}.json", name, std::process::id()));
+    MonitorConfig { from_block: Some(BLOCK), block_range: 4, confirmations: 0, state_path }
+}
+
+#[tokio::test]
+async fn early_outflows_are_recorded_with_their_transfers() {
+    let stub = RpcStub::new(BLOCK, TGE_TIMESTAMP + 10 * DAY);
+    let contract = ERC20::new(token(), stub.provider());
+    let config = config("early");
+    let mut state = MonitorState::default();
+    let multicall = MulticallConfig::default();
+    // Moving the unlocked 20,000 is allowed
+    holding(&stub, tokens(180_000));
+    transfer(&stub, wallet(), recipient(), tokens(20_000), BLOCK);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert!(found.is_empty());
+
+    // Another 50,000 leaves over two windows, plus a self-transfer that is ignored
+    holding(&stub, tokens(130_000));
+    transfer(&stub, wallet(), recipient(), tokens(30_000), BLOCK + 2);
+    transfer(&stub, wallet(), wallet(), tokens(1_000), BLOCK + 3);
+    transfer(&stub, wallet(), recipient(), tokens(20_000), BLOCK + 7);
+    transfer(&stub, recipient(), wallet(), tokens(5), BLOCK + 8);
+    stub.advance(BLOCK + 10, TGE_TIMESTAMP + 10 * DAY + 5);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+
+    assert_eq!(found.len(), 1);
+    let violation = &found[0];
+    assert_eq!(violation.label.name.as_deref(), Some("Team"));
+    assert_eq!((violation.block, violation.locked.as_str(), violation.balance.as_str(), violation.shortfall.as_str()), (BLOCK + 10, "180000", "130000", "50000"));
+    let outflows: Vec<(u64, &str)> = violation.outflows.iter().map(|outflow| (outflow.block, outflow.amount.as_str())).collect();
+    assert_eq!(outflows, [(BLOCK + 2, "30000"), (BLOCK + 7, "20000")]);
+    assert_eq!(violation.outflows[0].to, recipient());
+    assert_eq!(violation.outflows[0].transaction_hash, H256::from_low_u64_be(BLOCK + 2));
+
+    // The shortfall persists, but nothing new left the wallet
+    stub.advance(BLOCK + 20, TGE_TIMESTAMP + 10 * DAY + 10);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert!(found.is_empty());
+
+    // More tokens leave while still short
+    holding(&stub, tokens(120_000));
+    transfer(&stub, wallet(), recipient(), tokens(10_000), BLOCK + 25);
+    stub.advance(BLOCK + 30, TGE_TIMESTAMP + 10 * DAY + 15);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].shortfall, "60000");
+
+    assert_eq!(state.violations.len(), 2);
+    assert_eq!(state.last_checked_block, Some(BLOCK + 30));
+    let saved = MonitorState::load(&config.state_path).unwrap();
+    assert_eq!(saved.violations, state.violations);
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn shortfalls_found_on_the_first_check_are_recorded_once() {
+    let stub = RpcStub::new(BLOCK, TGE_TIMESTAMP + 10 * DAY).without_multicall();
+    let multicall = MulticallConfig::default().with_address(None);
+    holding(&stub, tokens(100_000));
+    let contract = ERC20::new(token(), stub.provider());
+    let config = MonitorConfig { from_block: None, ..config("first") };
+    let mut state = MonitorState::default();
+
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(found[0].outflows.is_empty());
+
+    stub.advance(BLOCK + 1, TGE_TIMESTAMP + 10 * DAY + 1);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert!(found.is_empty());
+
+    // Once the wallet is topped back up the next shortfall counts again
+    holding(&stub, tokens(180_000));
+    stub.advance(BLOCK + 2, TGE_TIMESTAMP + 10 * DAY + 2);
+    monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert!(state.open.is_empty());
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn transfers_are_attributed_once_confirmed() {
+    let stub = RpcStub::new(BLOCK + 10, TGE_TIMESTAMP + 10 * DAY);
+    let contract = ERC20::new(token(), stub.provider());
+    let config = MonitorConfig { confirmations: 5, ..config("confirmed") };
+    let mut state = MonitorState::default();
+    let multicall = MulticallConfig::default();
+    holding(&stub, tokens(170_000));
+    transfer(&stub, wallet(), recipient(), tokens(4_000), BLOCK + 2);
+    transfer(&stub, wallet(), recipient(), tokens(6_000), BLOCK + 8);
+
+    // The transfer 2 blocks from the head is not confirmed yet
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert_eq!(found.len(), 1);
+    let outflows: Vec<(u64, &str)> = found[0].outflows.iter().map(|outflow| (outflow.block, outflow.amount.as_str())).collect();
+    assert_eq!(outflows, [(BLOCK + 2, "4000")]);
+    assert_eq!(state.last_checked_block, Some(BLOCK + 5));
+
+    // Once confirmed it counts as a new outflow from the open shortfall
+    stub.advance(BLOCK + 15, TGE_TIMESTAMP + 10 * DAY + 5);
+    let found = monitor::check(&contract, &schedules(), U256::from(TGE_TIMESTAMP), &multicall, &config, &mut state, 18).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].outflows.iter().map(|outflow| outflow.block).collect::<Vec<_>>(), [BLOCK + 8]);
+    assert_eq!(state.last_checked_block, Some(BLOCK + 10));
+    std::fs::remove_file(&config.state_path).unwrap();
+}
+
+#[tokio::test]
+async fn transfers_between_a_schedules_wallets_are_not_outflows() {
+    let stub = RpcStub::new(BLOCK + 10, TGE_TIMESTAMP + 10 * DAY);
+    let contract = ERC20::new(token(), stub.provider());
+    let config = config("own_wallets");
+    let mut state = MonitorState::default();
+    let second = address(0x3003);
+    let schedules = vec![PoolSchedule { addresses: vec![(wallet(), "Matchain".to_string()), (second, "Matchain".to_string())], ..schedules().remove(0) }];
+    // 40,000 moved to the schedule's second wallet and 10,000 out of the schedule
+    holding(&stub, tokens(130_000));
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(second)], &[uint(tokens(40_000))]);
+    transfer(&stub, wallet(), second, tokens(40_000), BLOCK + 2);
+    transfer(&stub, wallet(), recipient(), tokens(10_000), BLOCK + 4);
+
+    let found = monitor::check(&contract, &schedules, U256::from(TGE_TIMESTAMP), &MulticallConfig::default(), &config, &mut state, 18).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].shortfall, "10000");
+    let outflows: Vec<(Address, &str)> = found[0].outflows.iter().map(|outflow| (outflow.to, outflow.amount.as_str())).collect();
+    assert_eq!(outflows, [(recipient(), "10000")]);
+    std::fs::remove_file(&config.state_path).unwrap();
+}
@@ -0,0 +1,71 @@
+// tests/openapi.rs
+use serde_json::Value;
+use std::process::Command;
+
+const README: &str = include_str!("../README.md");
+
+/// The document printed by `--openapi`, which needs no RPC or environment.
+fn document() -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_matchain-supply-apis")).arg("--openapi").env_clear().output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// Replaces every `{parameter}` with `{}`, since the README lists a parameter's values instead of
+/// its name.
+fn normalize(path: &str) -> String {
+    let mut normalized = String::new();
+    let mut in_parameter = false;
+    for c in path.chars() {
+        match c {
+            '{' => in_parameter = true,
+            '}' => {
+                in_parameter = false;
+                normalized.push_str("{}");
+            }
+            _ if !in_parameter => normalized.push(c),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(map) => {
+            found.extend(map.get("$ref").and_then(Value::as_str));
+            map.values().for_each(|value| refs(value, found));
+        }
+        Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+        _ => {}
+    }
+}
+
+#[test]
+fn every_readme_route_is_documented() {
+    let document = document();
+    let paths: Vec<String> = document["paths"].as_object().unwrap().keys().map(|path| normalize(path)).collect();
+    let routes: Vec<&str> = README.lines().filter_map(|line| line.strip_prefix("- `GET ")?.split('`').next()).collect();
+
+    assert!(routes.contains(&"/circulating-supply/breakdown") && routes.contains(&"/staking/pools/{address}/events"));
+    for route in routes {
+        assert!(paths.contains(&normalize(route)), "{} is missing from {:?}", route, paths);
+    }
+}
+
+#[test]
+fn responses_reference_defined_schemas() {
+    let document = document();
+    let schema = |path: &str| document["paths"][path]["get"]["responses"]["200"]["content"]["application/json"]["schema"]["$ref"].as_str().map(str::to_string);
+    assert_eq!(schema("/circulating-supply/breakdown").as_deref(), Some("#/components/schemas/SupplyBreakdown"));
+    assert_eq!(schema("/staking/pools/{address}/events").as_deref(), Some("#/components/schemas/EventHistory"));
+    assert_eq!(schema("/monitoring/violations").as_deref(), Some("#/components/schemas/ViolationReport"));
+
+    let mut found = Vec::new();
+    refs(&document, &mut found);
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+    for reference in found {
+        let name = reference.strip_prefix("#/components/schemas/").unwrap_or(reference);
+        assert!(schemas.contains_key(name), "{} is not defined", reference);
+    }
+}
@@ -0,0 +1,79 @@
+// tests/staking.rs
+mod common;
+
+use common::{RpcStub, address, tokens, uint};
+use ethers::abi::Token;
+use ethers::types::Address;
+use matchain_supply_apis::multicall::MulticallConfig;
+use matchain_supply_apis::staking;
+use std::collections::HashMap;
+
+const BLOCK: u64 = 10_000_000;
+const TIMESTAMP: u64 = 1_750_000_000;
+const UNBONDING_PERIOD: u64 = 1_000;
+
+fn pool() -> Address {
+    address(0x4000)
+}
+
+fn owner() -> Address {
+    address(0x6000)
+}
+
+fn delegator() -> Address {
+    address(0x6001)
+}
+
+fn unstake(amount: u64, block: u64) -> Token {
+    Token::Tuple(vec![uint(tokens(amount)), uint(block)])
+}
+
+/// A pool whose owner has 1,000 MAT unbonding and whose delegator has 3,000 unbonding and 500
+/// claimable.
+fn matchain() -> RpcStub {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    stub.respond(pool(), "owner()", &[], &[Token::Address(owner())])
+        .respond(pool(), "active()", &[], &[Token::Bool(true)])
+        .respond(pool(), "fee()", &[], &[uint(500u64)])
+        .respond(pool(), "ratio()", &[], &[uint(0u64)])
+        .respond(pool(), "stakers()", &[], &[uint(2u64)])
+        .respond(pool(), "totalStake()", &[], &[uint(tokens(25_000))])
+        .respond(pool(), "UNBONDING_PERIOD()", &[], &[uint(UNBONDING_PERIOD)])
+        .respond(pool(), "currentStake(address)", &[Token::Address(owner())], &[uint(tokens(10_000))])
+        .respond(pool(), "pendingUnstakes(address)", &[Token::Address(owner())], &[Token::Array(vec![unstake(1_000, BLOCK - 10)])])
+        .respond(
+            pool(),
+            "pendingUnstakes(address)",
+            &[Token::Address(delegator())],
+            &[Token::Array(vec![unstake(3_000, BLOCK - 10), unstake(500, BLOCK - 10 * UNBONDING_PERIOD)])],
+        );
+    stub
+}
+
+#[tokio::test]
+async fn pending_unstakes_cover_every_known_staker() {
+    let stub = matchain();
+    let stakers = HashMap::from([(pool(), vec![owner(), delegator()])]);
+    let readings = staking::read_staking_pools(stub.provider(), &[(pool(), false)], &stakers, &MulticallConfig::default(), BLOCK).await.unwrap();
+
+    let info = readings[0].to_info(18);
+    assert_eq!(info.owner_pending_unstakes, "1000");
+    assert_eq!(info.pending_unstakes.as_deref(), Some("4500"));
+    assert_eq!(info.unbonding.as_deref(), Some("4000"));
+    assert!(info.failures.is_empty(), "unexpected failures: {:?}", info.failures);
+
+    let summary = staking::summarize(&readings, BLOCK, 18);
+    assert_eq!((summary.pending_unstakes.as_deref(), summary.unbonding.as_deref()), (Some("4500"), Some("4000")));
+}
+
+#[tokio::test]
+async fn pending_unstakes_are_unknown_without_stakers() {
+    let stub = matchain();
+    let readings = staking::read_staking_pools(stub.provider(), &[(pool(), false)], &HashMap::new(), &MulticallConfig::default(), BLOCK).await.unwrap();
+
+    let info = readings[0].to_info(18);
+    assert_eq!(info.owner_pending_unstakes, "1000");
+    assert_eq!((info.pending_unstakes, info.unbonding), (None, None));
+    assert_eq!(readings[0].self_stake, tokens(10_000));
+    assert_eq!(staking::summarize(&readings, BLOCK, 18).pending_unstakes, None);
+}
@@ -0,0 +1,536 @@
+// tests/supply.rs
+mod common;
+
+use common::{BLOCK_TIME_MS, BLOCKS_PER_DAY, DAY, RpcStub, address, tokens, uint};
+use ethers::abi::Token;
+use ethers::providers::Provider;
+use ethers::types::{Address, U256};
+use matchain_supply_apis::ERC20;
+use matchain_supply_apis::allocations::{self, SupplyBasis};
+use matchain_supply_apis::config::{AllocationLabel, ExcludedAddress, PoolSchedule};
+use matchain_supply_apis::multicall::{CallFailure, MulticallConfig};
+use std::collections::HashMap;
+use matchain_supply_apis::supply::{self, DiscrepancyKind, MaxSupply, PoolFailurePolicy, PoolStatus, PoolVestingMode, ReleaseGranularity, StakedSupply, SupplyBreakdown, SupplyOptions, UnbondingTreatment};
+
+const BLOCK: u64 = 10_000_000;
+const TGE_TIMESTAMP: u64 = 1_750_000_000;
+// Ten days after TGE
+const TIMESTAMP: u64 = TGE_TIMESTAMP + 10 * DAY;
+const UNBONDING_PERIOD: u64 = 1_000;
+
+fn token() -> Address {
+    address(0x1000)
+}
+
+fn excluded() -> Address {
+    address(0x2000)
+}
+
+fn wallet() -> Address {
+    address(0x3000)
+}
+
+fn vesting_pool() -> Address {
+    address(0x4000)
+}
+
+fn reserved_pool() -> Address {
+    address(0x5000)
+}
+
+fn owner() -> Address {
+    address(0x6000)
+}
+
+fn delegator() -> Address {
+    address(0x6001)
+}
+
+/// Stakers of each pool as the staking indexer would report them.
+fn stakers() -> HashMap<Address, Vec<Address>> {
+    HashMap::from([(vesting_pool(), vec![owner(), delegator()]), (reserved_pool(), vec![owner()])])
+}
+
+/// Matchain with 1,000,000 MAT minted and 1,000 burned, one excluded wallet holding 100,000, a
+/// vesting wallet holding its whole 200,000 allocation, a vesting StakingPool with 10,000 self-stake and a reserved pool holding 50,000.
+fn matchain() -> RpcStub {
+    let stub = RpcStub::new(BLOCK, TIMESTAMP);
+    stub.respond(token(), "totalSupply()", &[], &[uint(tokens(1_000_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(Address::zero())], &[uint(tokens(1_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(excluded())], &[uint(tokens(100_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(wallet())], &[uint(tokens(200_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(vesting_pool())], &[uint(tokens(12_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(reserved_pool())], &[uint(tokens(50_000))]);
+
+    // Locked for 5 days, then vests over 50, so 10% has unlocked on day 10
+    stub.respond(vesting_pool(), "initialSelfStakeAmount()", &[], &[uint(tokens(10_000))])
+        .respond(vesting_pool(), "initialLockPeriod()", &[], &[uint(5 * BLOCKS_PER_DAY)])
+        .respond(vesting_pool(), "vestingDuration()", &[], &[uint(50 * BLOCKS_PER_DAY)])
+        .respond(vesting_pool(), "RATIO_PRECISION()", &[], &[uint(1_000_000u64)]);
+
+    for (pool, total_stake) in [(vesting_pool(), tokens(25_000)), (reserved_pool(), tokens(5_000))] {
+        stub.respond(pool, "owner()", &[], &[Token::Address(owner())])
+            .respond(pool, "active()", &[], &[Token::Bool(true)])
+            .respond(pool, "fee()", &[], &[uint(500u64)])
+            .respond(pool, "ratio()", &[], &[uint(0u64)])
+            .respond(pool, "stakers()", &[], &[uint(3u64)])
+            .respond(pool, "totalStake()", &[], &[uint(total_stake)])
+            .respond(pool, "UNBONDING_PERIOD()", &[], &[uint(UNBONDING_PERIOD)])
+            .respond(pool, "currentStake(address)", &[Token::Address(owner())], &[uint(tokens(10_000))])
+            .respond(pool, "pendingUnstakes(address)", &[Token::Address(owner())], &[Token::Array(Vec::new())])
+            .respond(pool, "pendingUnstakes(address)", &[Token::Address(delegator())], &[Token::Array(Vec::new())]);
+    }
+    stub
+}
+
+fn excluded_addresses() -> Vec<ExcludedAddress> {
+    let treasury = AllocationLabel { name: Some("Treasury".to_string()), category: Some("treasury".to_string()) };
+    vec![
+        ExcludedAddress { address: excluded(), chain: "Matchain".to_string(), label: treasury },
+        ExcludedAddress { address: address(0x7000), chain: "BSC".to_string(), label: AllocationLabel::default() },
+    ]
+}
+
+/// 200,000 MAT with 10% at TGE and a 30 day cliff, so 180,000 is still locked on day 10.
+fn wallet_schedules() -> Vec<PoolSchedule> {
+    vec![PoolSchedule {
+        addresses: vec![(wallet(), "Matchain".to_string())],
+        tge_percentage: U256::from(10),
+        cliff: U256::from(30),
+        vesting: U256::from(300),
+        vesting_type: "linear".to_string(),
+        balance_at_tge: tokens(200_000),
+        release_granularity: ReleaseGranularity::Continuous,
+        tranches: Vec::new(),
+        start_timestamp: None,
+        label: AllocationLabel { name: Some("Team".to_string()), category: Some("team".to_string()) },
+    }]
+}
+
+fn options() -> SupplyOptions {
+    SupplyOptions {
+        tge_timestamp: U256::from(TGE_TIMESTAMP),
+        pool_failure_policy: PoolFailurePolicy::Locked,
+        pool_vesting_mode: PoolVestingMode::Replay,
+        pool_cross_check: false,
+        block_time_ms: BLOCK_TIME_MS,
+        pool_release_granularity: ReleaseGranularity::Daily,
+        unbonding: UnbondingTreatment::Off,
+    }
+}
+
+async fn circulating(stub: &RpcStub, multicall: MulticallConfig, options: &SupplyOptions) -> Result<SupplyBreakdown, anyhow::Error> {
+    let contract: ERC20<Provider<RpcStub>> = ERC20::new(token(), stub.provider());
+    let multicall = multicall.detect(contract.client().as_ref(), "Matchain").await?;
+    supply::get_circulating_supply(
+        &contract,
+        &excluded_addresses(),
+        &wallet_schedules(),
+        &[(vesting_pool(), false), (reserved_pool(), true)],
+        &stakers(),
+        &multicall,
+        options,
+        18,
+    )
+    .await
+}
+
+async fn staked(stub: &RpcStub, breakdown: &SupplyBreakdown) -> (StakedSupply, Vec<CallFailure>) {
+    supply::get_staked_supply(stub.provider(), breakdown, &[(vesting_pool(), false), (reserved_pool(), true)], &MulticallConfig::default(), 18).await.unwrap()
+}
+
+#[tokio::test]
+async fn circulating_supply_subtracts_excluded_and_locked_balances() {
+    let stub = matchain();
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    assert_eq!(breakdown.total_supply, "999000");
+    assert_eq!(breakdown.excluded_balance, "100000");
+    // 180,000 wallet + 9,000 vesting pool + 50,000 reserved pool
+    assert_eq!(breakdown.locked_balance, "239000");
+    assert_eq!(breakdown.circulating_supply, "660000");
+    assert_eq!(breakdown.circulating_supply_wei, tokens(660_000));
+    assert!(breakdown.staked.is_none());
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+
+    let (staked, failures) = staked(&stub, &breakdown).await;
+    assert_eq!((staked.total_staked.as_str(), staked.staked_percent_of_circulating.as_str(), staked.pools), ("30000", "4.54", 2));
+    assert!(failures.is_empty(), "unexpected failures: {:?}", failures);
+
+    let statuses: Vec<PoolStatus> = breakdown.pools.iter().map(|pool| pool.status).collect();
+    assert_eq!(statuses, [PoolStatus::Vesting, PoolStatus::Reserved]);
+    assert_eq!(breakdown.pools[0].days_passed, 10);
+    assert_eq!(breakdown.pools[0].locked, "9000");
+    assert!(breakdown.schedules[0].discrepancy.is_none());
+    assert!(stub.aggregate3_calls() > 0);
+}
+
+#[tokio::test]
+async fn breakdown_totals_allocations_per_category() {
+    let stub = matchain();
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(address(0x3001))], &[uint(tokens(50_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(address(0x3002))], &[uint(tokens(200_000))]);
+    let mut schedules = wallet_schedules();
+    schedules.push(PoolSchedule {
+        addresses: vec![(address(0x3001), "Matchain".to_string())],
+        balance_at_tge: tokens(50_000),
+        label: AllocationLabel { name: Some("Advisors".to_string()), category: Some("team".to_string()) },
+        ..wallet_schedules().remove(0)
+    });
+    schedules.push(PoolSchedule { addresses: vec![(address(0x3002), "Matchain".to_string())], label: AllocationLabel::default(), ..wallet_schedules().remove(0) });
+
+    let contract: ERC20<Provider<RpcStub>> = ERC20::new(token(), stub.provider());
+    let breakdown = supply::get_circulating_supply(&contract, &excluded_addresses(), &schedules, &[], &HashMap::new(), &MulticallConfig::default(), &options(), 18).await.unwrap();
+
+    let categories: Vec<(&str, &str, &str, &str)> =
+        breakdown.categories.iter().map(|c| (c.category.as_str(), c.allocated.as_str(), c.locked.as_str(), c.excluded_balance.as_str())).collect();
+    assert_eq!(categories, [("team", "250000", "225000", "0"), ("uncategorized", "200000", "180000", "0"), ("treasury", "0", "0", "100000")]);
+    assert_eq!(breakdown.excluded[0].label.name.as_deref(), Some("Treasury"));
+    assert_eq!(breakdown.schedules[1].label.name.as_deref(), Some("Advisors"));
+
+    let report = allocations::allocations_report(&breakdown, SupplyBasis::Max, tokens(1_000_000), 18);
+    let shares: Vec<&str> = report.allocations.iter().map(|allocation| allocation.share_percent.as_str()).collect();
+    assert_eq!(shares, ["20", "5", "20"]);
+    assert_eq!(report.categories[0].share_percent, "25");
+    assert_eq!(report.allocations[0].unlocked, "20000");
+}
+
+#[tokio::test]
+async fn schedules_starting_after_now_are_fully_locked() {
+    let stub = matchain();
+    let contract: ERC20<Provider<RpcStub>> = ERC20::new(token(), stub.provider());
+    let multicall = MulticallConfig::default().detect(contract.client().as_ref(), "Matchain").await.unwrap();
+    let start = TIMESTAMP + DAY;
+    let schedules = vec![PoolSchedule { start_timestamp: Some(U256::from(start)), ..wallet_schedules().remove(0) }];
+    let breakdown = supply::get_circulating_supply(&contract, &excluded_addresses(), &schedules, &[(vesting_pool(), false), (reserved_pool(), true)], &stakers(), &multicall, &options(), 18)
+        .await
+        .unwrap();
+
+    // The TGE share stays locked until the schedule starts
+    assert_eq!(breakdown.schedules[0].locked, "200000");
+    assert_eq!(breakdown.schedules[0].start_timestamp, start);
+    assert_eq!(breakdown.schedules[0].days_until_vesting_ends, 330);
+    assert_eq!(breakdown.circulating_supply, "640000");
+}
+
+#[tokio::test]
+async fn locked_amounts_are_capped_at_wallet_balances() {
+    // The wallet moved out 50,000 of its 180,000 locked tokens
+    let stub = matchain();
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(wallet())], &[uint(tokens(130_000))]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    let schedule = &breakdown.schedules[0];
+    assert_eq!(schedule.balance.as_deref(), Some("130000"));
+    assert_eq!(schedule.locked, "130000");
+    let discrepancy = schedule.discrepancy.as_ref().unwrap();
+    assert_eq!(discrepancy.kind, DiscrepancyKind::BelowLocked);
+    assert_eq!((discrepancy.expected.as_str(), discrepancy.difference.as_str()), ("180000", "50000"));
+    assert_eq!(breakdown.circulating_supply, "710000");
+}
+
+#[tokio::test]
+async fn extra_wallet_tokens_are_reported() {
+    let stub = matchain().without_multicall();
+    stub.respond(token(), "balanceOf(address)", &[Token::Address(wallet())], &[uint(tokens(250_000))]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    let schedule = &breakdown.schedules[0];
+    assert_eq!(schedule.locked, "180000");
+    let discrepancy = schedule.discrepancy.as_ref().unwrap();
+    assert_eq!(discrepancy.kind, DiscrepancyKind::AboveAllocation);
+    assert_eq!((discrepancy.expected.as_str(), discrepancy.difference.as_str()), ("200000", "50000"));
+    assert_eq!(breakdown.circulating_supply, "660000");
+}
+
+#[tokio::test]
+async fn direct_calls_match_multicall() {
+    let stub = matchain().without_multicall();
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    assert_eq!(breakdown.circulating_supply, "660000");
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+    assert_eq!(stub.aggregate3_calls(), 0);
+}
+
+#[tokio::test]
+async fn duplicate_pool_entries_are_read_once() {
+    let stub = matchain();
+    let contract: ERC20<Provider<RpcStub>> = ERC20::new(token(), stub.provider());
+    let pools = [(vesting_pool(), false), (vesting_pool(), false), (reserved_pool(), true), (reserved_pool(), false)];
+    let breakdown = supply::get_circulating_supply(&contract, &excluded_addresses(), &wallet_schedules(), &pools, &stakers(), &MulticallConfig::default(), &options(), 18).await.unwrap();
+
+    let pools: Vec<(Address, PoolStatus, &str)> = breakdown.pools.iter().map(|pool| (pool.address, pool.status, pool.locked.as_str())).collect();
+    assert_eq!(pools, [(vesting_pool(), PoolStatus::Vesting, "9000"), (reserved_pool(), PoolStatus::Reserved, "50000")]);
+    assert_eq!(breakdown.circulating_supply, "660000");
+}
+
+#[tokio::test]
+async fn replayed_locks_are_not_rounded_to_whole_days() {
+    // Locked for 10.5 days, then vests continuously over 50
+    let stub = matchain();
+    stub.respond(vesting_pool(), "initialLockPeriod()", &[], &[uint(10 * BLOCKS_PER_DAY + BLOCKS_PER_DAY / 2)]);
+    let options = SupplyOptions { pool_release_granularity: ReleaseGranularity::Continuous, ..options() };
+    let pool_locked = |breakdown: &SupplyBreakdown| breakdown.pools[0].locked.clone();
+
+    stub.advance(BLOCK + BLOCKS_PER_DAY / 4, TIMESTAMP + DAY / 4);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+    assert_eq!(pool_locked(&breakdown), "10000");
+    assert_eq!(breakdown.pools[0].lock_days, 10);
+
+    // Half a day into vesting 1% has unlocked
+    stub.advance(BLOCK + BLOCKS_PER_DAY, TIMESTAMP + DAY);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+    assert_eq!(pool_locked(&breakdown), "9900");
+}
+
+#[tokio::test]
+async fn small_batches_match_a_single_batch() {
+    let stub = matchain();
+    let breakdown = circulating(&stub, MulticallConfig::default().with_max_calls_per_batch(2), &options()).await.unwrap();
+
+    assert_eq!(breakdown.circulating_supply, "660000");
+    assert!(stub.aggregate3_calls() > 3);
+}
+
+#[tokio::test]
+async fn failed_pool_is_assumed_locked_at_its_balance() {
+    let stub = matchain();
+    stub.revert(vesting_pool(), "initialLockPeriod()", &[]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    assert_eq!(breakdown.pools[0].status, PoolStatus::AssumedLocked);
+    assert_eq!(breakdown.pools[0].locked, "12000");
+    assert_eq!(breakdown.circulating_supply, "657000");
+    assert_eq!(breakdown.failures.len(), 1);
+    assert_eq!(breakdown.failures[0].target, vesting_pool());
+    assert_eq!(breakdown.failures[0].function, "initialLockPeriod");
+}
+
+#[tokio::test]
+async fn zero_ratio_precision_is_a_failed_pool_read() {
+    let stub = matchain();
+    stub.respond(vesting_pool(), "RATIO_PRECISION()", &[], &[uint(0u64)]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    assert_eq!(breakdown.pools[0].status, PoolStatus::AssumedLocked);
+    assert_eq!(breakdown.circulating_supply, "657000");
+    let failures: Vec<(Address, &str, &str)> = breakdown.failures.iter().map(|failure| (failure.target, failure.function.as_str(), failure.reason.as_str())).collect();
+    assert_eq!(failures, [(vesting_pool(), "RATIO_PRECISION", "returned zero")]);
+}
+
+#[tokio::test]
+async fn failed_pool_can_be_skipped() {
+    let stub = matchain();
+    stub.revert(vesting_pool(), "vestingDuration()", &[]);
+    let options = SupplyOptions { pool_failure_policy: PoolFailurePolicy::Skip, ..options() };
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+
+    assert_eq!(breakdown.pools[0].status, PoolStatus::Skipped);
+    assert_eq!(breakdown.circulating_supply, "669000");
+}
+
+#[tokio::test]
+async fn failed_pool_fails_the_request_under_fail_policy() {
+    let stub = matchain();
+    stub.revert(vesting_pool(), "initialSelfStakeAmount()", &[]);
+    let options = SupplyOptions { pool_failure_policy: PoolFailurePolicy::Fail, ..options() };
+    let error = circulating(&stub, MulticallConfig::default(), &options).await.unwrap_err();
+
+    assert!(error.to_string().contains("Failed to read vesting parameters"), "{}", error);
+}
+
+#[tokio::test]
+async fn failed_token_read_fails_the_request() {
+    for stub in [matchain(), matchain().without_multicall()] {
+        stub.revert(token(), "totalSupply()", &[]);
+        assert!(circulating(&stub, MulticallConfig::default(), &options()).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn failed_cross_checks_are_kept_out_of_supply_failures() {
+    let stub = matchain();
+    let options = SupplyOptions { pool_cross_check: true, ..options() };
+    stub.respond(vesting_pool(), "getAvailableSelfStake()", &[], &[uint(tokens(1_000))]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+    let cross_check = breakdown.pools[0].cross_check.as_ref().unwrap();
+    assert!(cross_check.matches && cross_check.failure.is_none());
+
+    stub.revert(vesting_pool(), "getAvailableSelfStake()", &[]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+    let cross_check = breakdown.pools[0].cross_check.as_ref().unwrap();
+    assert_eq!((cross_check.contract_available.as_deref(), cross_check.matches), (None, false));
+    assert_eq!(cross_check.failure.as_ref().map(|failure| failure.function.as_str()), Some("getAvailableSelfStake"));
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+    assert_eq!(breakdown.circulating_supply, "660000");
+}
+
+/// Contract mode reading a 5 day lock and 50 days of vesting for a pool created at `creation`.
+fn contract_pool(stub: &RpcStub, creation: u64) {
+    stub.respond(vesting_pool(), "getVestingSchedule()", &[], &[uint(5u64), uint(50u64)])
+        .respond(vesting_pool(), "poolCreation()", &[], &[uint(creation)])
+        .respond(vesting_pool(), "BLOCKS_PER_DAY()", &[], &[uint(BLOCKS_PER_DAY)]);
+}
+
+#[tokio::test]
+async fn contract_schedules_count_days_of_blocks_from_pool_creation() {
+    let stub = matchain();
+    let options = SupplyOptions { pool_vesting_mode: PoolVestingMode::Contract, pool_cross_check: true, ..options() };
+    let creation = BLOCK - 20 * BLOCKS_PER_DAY;
+    contract_pool(&stub, creation);
+    stub.respond(vesting_pool(), "getAvailableSelfStake()", &[], &[uint(tokens(3_500))]);
+
+    // Locked through day 5, then 1/50 of the 10,000 self-stake unlocks each day
+    for (block, locked) in [(creation + 5 * BLOCKS_PER_DAY - 1, "10000"), (BLOCK, "7000"), (creation + 55 * BLOCKS_PER_DAY, "0")] {
+        stub.advance(block, TIMESTAMP);
+        let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+        let pool = &breakdown.pools[0];
+        assert_eq!((pool.schedule_source, pool.start, pool.lock_days, pool.vesting_days), (PoolVestingMode::Contract, creation, 5, 50));
+        assert_eq!(pool.locked, locked, "at block {}", block);
+    }
+
+    // The contract reports 500 more available than computed on day 20
+    stub.advance(BLOCK, TIMESTAMP);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+    let cross_check = breakdown.pools[0].cross_check.as_ref().unwrap();
+    assert_eq!((cross_check.computed_available.as_str(), cross_check.contract_available.as_deref()), ("3000", Some("3500")));
+    assert_eq!((cross_check.difference.as_deref(), cross_check.matches), (Some("500"), false));
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+}
+
+#[tokio::test]
+async fn contract_schedules_must_start_at_a_past_block() {
+    let stub = matchain();
+    let options = SupplyOptions { pool_vesting_mode: PoolVestingMode::Contract, ..options() };
+    // A creation timestamp rather than a block number
+    contract_pool(&stub, TGE_TIMESTAMP);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+
+    assert_eq!(breakdown.pools[0].status, PoolStatus::AssumedLocked);
+    let failures: Vec<(Address, &str)> = breakdown.failures.iter().map(|failure| (failure.target, failure.function.as_str())).collect();
+    assert_eq!(failures, [(vesting_pool(), "poolCreation")]);
+}
+
+#[tokio::test]
+async fn undecodable_pool_reads_are_pool_failures() {
+    let options = SupplyOptions { pool_vesting_mode: PoolVestingMode::Contract, ..options() };
+    for stub in [matchain(), matchain().without_multicall()] {
+        // One word where getVestingSchedule() returns two
+        contract_pool(&stub, BLOCK - 20 * BLOCKS_PER_DAY);
+        stub.respond(vesting_pool(), "getVestingSchedule()", &[], &[uint(5u64)]);
+        let breakdown = circulating(&stub, MulticallConfig::default(), &options).await.unwrap();
+
+        assert_eq!(breakdown.pools[0].status, PoolStatus::AssumedLocked);
+        let failures: Vec<(Address, &str)> = breakdown.failures.iter().map(|failure| (failure.target, failure.function.as_str())).collect();
+        assert_eq!(failures, [(vesting_pool(), "getVestingSchedule")]);
+        assert_eq!(breakdown.circulating_supply, "657000");
+    }
+}
+
+#[tokio::test]
+async fn short_multicall_responses_fail_the_request() {
+    let stub = matchain();
+    stub.truncate_multicall(1);
+    let error = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap_err();
+    assert!(error.to_string().contains("Multicall returned 1 results"), "{}", error);
+
+    let contract = ERC20::new(token(), stub.provider());
+    let error = supply::get_total_supply(&contract, &contract, &MulticallConfig::default(), &MulticallConfig::default(), 18).await.unwrap_err();
+    assert!(error.to_string().contains("for 2 calls"), "{}", error);
+}
+
+#[tokio::test]
+async fn failed_staking_reads_are_reported_without_failing() {
+    let stub = matchain();
+    stub.revert(reserved_pool(), "totalStake()", &[]);
+    let breakdown = circulating(&stub, MulticallConfig::default(), &options()).await.unwrap();
+
+    assert_eq!(breakdown.circulating_supply, "660000");
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+
+    let (staked, failures) = staked(&stub, &breakdown).await;
+    assert_eq!(staked.total_staked, "25000");
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].function, "totalStake");
+}
+
+#[tokio::test]
+async fn unbonding_unstakes_can_be_excluded() {
+    let stub = matchain();
+    let unstake = |amount: U256, block: u64| Token::Tuple(vec![uint(amount), uint(block)]);
+    // The owner's first unstake is still unbonding; the second matured long ago
+    stub.respond(
+        vesting_pool(),
+        "pendingUnstakes(address)",
+        &[Token::Address(owner())],
+        &[Token::Array(vec![unstake(tokens(2_000), BLOCK - 10), unstake(tokens(500), BLOCK - 10 * UNBONDING_PERIOD)])],
+    )
+    .respond(vesting_pool(), "pendingUnstakes(address)", &[Token::Address(delegator())], &[Token::Array(vec![unstake(tokens(1_000), BLOCK - 20)])])
+    // Already counted as locked through the reserved pool's balance
+    .respond(reserved_pool(), "pendingUnstakes(address)", &[Token::Address(owner())], &[Token::Array(vec![unstake(tokens(7_000), BLOCK - 10)])]);
+
+    let circulating_options = SupplyOptions { unbonding: UnbondingTreatment::Circulating, ..options() };
+    let breakdown = circulating(&stub, MulticallConfig::default(), &circulating_options).await.unwrap();
+    assert_eq!(breakdown.unbonding_balance.as_deref(), Some("3000"));
+    assert_eq!(breakdown.circulating_supply, "660000");
+
+    let excluded_options = SupplyOptions { unbonding: UnbondingTreatment::Excluded, ..options() };
+    let breakdown = circulating(&stub, MulticallConfig::default(), &excluded_options).await.unwrap();
+    assert_eq!(breakdown.circulating_supply, "657000");
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+
+    assert!(breakdown.unbonding_incomplete.is_empty());
+
+    // Without the pool's stakers only the owner's unstakes are known, which is reported without
+    // counting as a failed read
+    let contract: ERC20<Provider<RpcStub>> = ERC20::new(token(), stub.provider());
+    let pools = [(vesting_pool(), false), (reserved_pool(), true)];
+    let breakdown = supply::get_circulating_supply(&contract, &excluded_addresses(), &wallet_schedules(), &pools, &HashMap::new(), &MulticallConfig::default(), &excluded_options, 18).await.unwrap();
+    assert_eq!(breakdown.unbonding_balance.as_deref(), Some("2000"));
+    assert_eq!(breakdown.unbonding_incomplete, [vesting_pool()]);
+    assert!(breakdown.failures.is_empty(), "unexpected failures: {:?}", breakdown.failures);
+}
+
+#[tokio::test]
+async fn total_supply_nets_burns_on_both_chains() {
+    let matchain = matchain();
+    let bsc = RpcStub::new(50_000_000, TIMESTAMP).without_multicall();
+    bsc.respond(token(), "totalSupply()", &[], &[uint(tokens(300_000))])
+        .respond(token(), "balanceOf(address)", &[Token::Address(Address::zero())], &[uint(tokens(500))]);
+
+    let matchain_contract = ERC20::new(token(), matchain.provider());
+    let bsc_contract = ERC20::new(token(), bsc.provider());
+    let bsc_multicall = MulticallConfig::default().detect(bsc_contract.client().as_ref(), "BSC").await.unwrap();
+    let total = supply::get_total_supply(&matchain_contract, &bsc_contract, &MulticallConfig::default(), &bsc_multicall, 18).await.unwrap();
+
+    assert_eq!(total, "1298500");
+    assert_eq!(bsc.aggregate3_calls(), 0);
+}
+
+#[test]
+fn max_supply_parses_cap_or_whole_tokens() {
+    assert_eq!(MaxSupply::parse("cap", 18).unwrap(), MaxSupply::Cap);
+    assert_eq!(MaxSupply::parse(" CAP ", 18).unwrap(), MaxSupply::Cap);
+    assert_eq!(MaxSupply::parse("1000000000", 18).unwrap(), MaxSupply::Fixed(tokens(1_000_000_000)));
+    assert_eq!(MaxSupply::parse("1.5", 6).unwrap(), MaxSupply::Fixed(U256::from(1_500_000u64)));
+    for invalid in ["lots", "", "1.0000001", "-1"] {
+        assert!(MaxSupply::parse(invalid, 6).is_err(), "{:?} parsed as {:?}", invalid, MaxSupply::parse(invalid, 6));
+    }
+}
+
+#[tokio::test]
+async fn mintable_supply_is_max_minus_minted_and_never_negative() {
+    let stub = matchain();
+    stub.respond(token(), "cap()", &[], &[uint(tokens(2_000_000))]);
+    let contract = ERC20::new(token(), stub.provider());
+    let multicall = MulticallConfig::default();
+    let mintable = |max_supply| supply::get_mintable_supply_wei(&contract, &contract, &multicall, &multicall, max_supply);
+
+    // The cap only covers Matchain's 1,000,000 minted, burns included
+    assert_eq!(mintable(MaxSupply::Cap).await.unwrap(), Some(tokens(1_000_000)));
+    // A fixed max supply covers 999,000 MAT net of burns on each of the two chains
+    assert_eq!(mintable(MaxSupply::Fixed(tokens(2_000_000))).await.unwrap(), Some(tokens(2_000)));
+    assert_eq!(mintable(MaxSupply::Fixed(tokens(1_000_000))).await.unwrap(), Some(U256::zero()));
+    assert_eq!(mintable(MaxSupply::Unset).await.unwrap(), None);
+}
@@ -0,0 +1,57 @@
+// tests/utils.rs
+use ethers::types::U256;
+use matchain_supply_apis::utils::{AmountFormat, MAX_DECIMAL_PLACES, Rounding, Unit};
+
+/// `whole.fraction` with 18 decimals.
+fn wei(value: &str) -> U256 {
+    ethers::utils::parse_units(value, 18).unwrap().into()
+}
+
+fn format(decimals: Option<u8>, rounding: Rounding, value: U256) -> String {
+    AmountFormat { unit: Unit::Token, decimals, rounding }.format(value, 18)
+}
+
+#[test]
+fn default_format_prints_every_significant_digit() {
+    assert_eq!(AmountFormat::default().format(wei("1234.5600001"), 18), "1234.5600001");
+    assert_eq!(AmountFormat::default().format(wei("1000"), 18), "1000");
+}
+
+#[test]
+fn wei_ignores_decimals_and_rounding() {
+    let format = AmountFormat { unit: Unit::Wei, decimals: Some(2), rounding: Rounding::Up };
+    assert_eq!(format.format(wei("1.5"), 18), "1500000000000000000");
+}
+
+#[test]
+fn decimals_are_rounded_as_requested() {
+    let value = wei("2.345");
+    assert_eq!(format(Some(2), Rounding::Down, value), "2.34");
+    assert_eq!(format(Some(2), Rounding::Up, value), "2.35");
+    assert_eq!(format(Some(2), Rounding::HalfUp, value), "2.35");
+    assert_eq!(format(Some(2), Rounding::HalfUp, wei("2.344999999999999999")), "2.34");
+    // Exact values are never rounded up
+    assert_eq!(format(Some(2), Rounding::Up, wei("2.34")), "2.34");
+    assert_eq!(format(Some(0), Rounding::HalfUp, wei("2.5")), "3");
+    assert_eq!(format(Some(0), Rounding::Down, wei("2.5")), "2");
+}
+
+#[test]
+fn rounding_up_carries_into_the_integer_part() {
+    assert_eq!(format(Some(2), Rounding::Up, wei("9.991")), "10.00");
+    assert_eq!(format(Some(2), Rounding::HalfUp, wei("99.995")), "100.00");
+    assert_eq!(format(Some(0), Rounding::Up, U256::one()), "1");
+}
+
+#[test]
+fn decimals_past_the_token_are_padded_and_capped() {
+    assert_eq!(format(Some(20), Rounding::Down, wei("1.5")), "1.50000000000000000000");
+    let capped = format(Some(60), Rounding::Down, wei("1.5"));
+    assert_eq!(capped.split_once('.').unwrap().1.len(), MAX_DECIMAL_PLACES as usize);
+}
+
+#[test]
+fn options_use_snake_case_names() {
+    let format: AmountFormat = serde_json::from_str(r#"{ "unit": "wei", "rounding": "half_up" }"#).unwrap();
+    assert_eq!(format, AmountFormat { unit: Unit::Wei, decimals: None, rounding: Rounding::HalfUp });
+}
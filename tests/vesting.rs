@@ -0,0 +1,372 @@
+// tests/vesting.rs
+use ethers::types::U256;
+use matchain_supply_apis::config::{self, PoolSchedule, TrancheTime, TranchePoint};
+use matchain_supply_apis::supply::{PoolCalculation, ReleaseGranularity, TimeUnit, calculate_pool_vesting, calculate_schedule_vesting};
+use matchain_supply_apis::vesting::{CliffOnly, Elapsed, Piecewise, Schedule};
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/vesting_schedules.json");
+const RATIO_PRECISION: u64 = 1_000_000;
+const DAY: u64 = 86_400;
+const TGE: u64 = 1_750_000_000;
+
+fn tokens(n: u64) -> U256 {
+    U256::from(n) * U256::exp10(18)
+}
+
+fn vest(initial: U256, tge_percentage: u64, cliff: u64, vesting: u64, seconds: u64, granularity: ReleaseGranularity, vesting_type: &str) -> PoolCalculation {
+    calculate_pool_vesting(
+        initial,
+        U256::from(tge_percentage),
+        U256::from(cliff),
+        U256::from(vesting),
+        U256::from(RATIO_PRECISION),
+        U256::from(TGE + seconds),
+        U256::from(TGE),
+        TimeUnit::Seconds,
+        granularity,
+        vesting_type,
+    )
+}
+
+fn vest_schedule(schedule: &PoolSchedule, seconds: u64) -> PoolCalculation {
+    calculate_schedule_vesting(schedule, U256::from(RATIO_PRECISION), U256::from(TGE + seconds), U256::from(TGE))
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct GoldenSchedule {
+    schedule: usize,
+    vesting_type: String,
+    tge_percentage: u64,
+    cliff: u64,
+    vesting: u64,
+    release_granularity: ReleaseGranularity,
+    initial: String,
+    points: Vec<GoldenPoint>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct GoldenPoint {
+    label: String,
+    seconds_since_tge: u64,
+    locked: String,
+    unlocked_fraction: u64,
+}
+
+/// TGE, both sides of the cliff, every 90-day step of stepped schedules, the middle and end of
+/// linear vesting, and ten years after vesting ends.
+fn key_dates(schedule: &PoolSchedule) -> Vec<(String, u64)> {
+    let cliff = schedule.cliff.as_u64();
+    let vesting = schedule.vesting.as_u64();
+    let end = cliff + vesting;
+
+    let mut dates = vec![("tge".to_string(), 0)];
+    if cliff > 0 {
+        dates.push(("day before cliff end".to_string(), (cliff - 1) * DAY));
+    }
+    dates.push(("cliff end".to_string(), cliff * DAY));
+    if schedule.vesting_type == "stepped" {
+        for step in 1..=vesting / 90 {
+            dates.push((format!("day before step {}", step), (cliff + step * 90 - 1) * DAY));
+            dates.push((format!("step {}", step), (cliff + step * 90) * DAY));
+        }
+    } else if vesting > 0 {
+        dates.push(("mid vesting".to_string(), (cliff + vesting / 2) * DAY + DAY / 2));
+        dates.push(("day before vesting end".to_string(), (end - 1) * DAY));
+    }
+    dates.push(("vesting end".to_string(), end * DAY));
+    dates.push(("far future".to_string(), (end + 3_650) * DAY));
+    dates
+}
+
+fn golden_schedules() -> Vec<GoldenSchedule> {
+    config::read_pool_data()
+        .iter()
+        .enumerate()
+        .map(|(i, schedule)| GoldenSchedule {
+            schedule: i,
+            vesting_type: schedule.vesting_type.clone(),
+            tge_percentage: schedule.tge_percentage.as_u64(),
+            cliff: schedule.cliff.as_u64(),
+            vesting: schedule.vesting.as_u64(),
+            release_granularity: schedule.release_granularity,
+            initial: schedule.balance_at_tge.to_string(),
+            points: key_dates(schedule)
+                .into_iter()
+                .map(|(label, seconds)| {
+                    let calc = vest_schedule(schedule, seconds);
+                    GoldenPoint { label, seconds_since_tge: seconds, locked: calc.locked_amount.to_string(), unlocked_fraction: calc.unlocked_fraction.as_u64() }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Compares every configured schedule at its key dates with the checked-in fixture. Run with
+/// `UPDATE_GOLDEN=1` to rewrite the fixture after an intended change, then review the diff.
+#[test]
+fn configured_schedules_match_golden_file() {
+    let actual = golden_schedules();
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        std::fs::write(GOLDEN_PATH, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+        return;
+    }
+
+    let expected: Vec<GoldenSchedule> = serde_json::from_str(&std::fs::read_to_string(GOLDEN_PATH).unwrap()).unwrap();
+    assert_eq!(actual.len(), expected.len(), "schedule count changed; rerun with UPDATE_GOLDEN=1 if intended");
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_eq!(actual, expected, "schedule {} differs from {}", actual.schedule, GOLDEN_PATH);
+    }
+}
+
+/// (description, tge %, cliff, vesting, seconds since TGE, granularity, type, unlocked fraction)
+type BranchCase = (&'static str, u64, u64, u64, u64, ReleaseGranularity, &'static str, u64);
+
+#[test]
+fn vesting_branches() {
+    use ReleaseGranularity::{Continuous, Daily};
+
+    let cases: &[BranchCase] = &[
+        ("linear at TGE releases the TGE share", 10, 30, 300, 0, Continuous, "linear", 100_000),
+        ("linear before cliff holds the TGE share", 10, 30, 300, 29 * DAY, Continuous, "linear", 100_000),
+        ("linear at cliff end starts from the TGE share", 10, 30, 300, 30 * DAY, Continuous, "linear", 100_000),
+        ("linear adds vesting progress on top of TGE", 0, 30, 300, 180 * DAY, Continuous, "linear", 500_000),
+        ("linear continuous counts partial days", 0, 0, 100, DAY / 2, Continuous, "linear", 5_000),
+        ("linear daily ignores partial days", 0, 0, 100, DAY / 2, Daily, "linear", 0),
+        ("linear caps at fully unlocked", 35, 90, 1440, 10_000 * DAY, Continuous, "linear", 1_000_000),
+        ("zero vesting never goes past the TGE share", 20, 30, 0, 10_000 * DAY, Continuous, "linear", 200_000),
+        ("zero cliff and vesting with full TGE unlocks everything", 100, 0, 0, 0, Continuous, "linear", 1_000_000),
+        ("stepped before cliff holds the TGE share", 20, 30, 540, 29 * DAY, Continuous, "stepped", 200_000),
+        ("stepped at cliff end has no step yet", 20, 30, 540, 30 * DAY, Continuous, "stepped", 200_000),
+        ("stepped releases 16.67% of the remainder per step", 20, 30, 540, 120 * DAY, Continuous, "stepped", 333_360),
+        ("stepped day before second step", 20, 30, 540, 209 * DAY, Continuous, "stepped", 333_360),
+        ("stepped second step", 20, 30, 540, 210 * DAY, Continuous, "stepped", 444_488),
+        // Each step releases a share of what remains, so six steps leave about a quarter locked
+        ("stepped stops after six steps", 20, 30, 540, 10_000 * DAY, Continuous, "stepped", 732_144),
+    ];
+
+    for &(description, tge, cliff, vesting, seconds, granularity, vesting_type, unlocked_fraction) in cases {
+        let calc = vest(tokens(1_000_000), tge, cliff, vesting, seconds, granularity, vesting_type);
+        assert_eq!(calc.unlocked_fraction, U256::from(unlocked_fraction), "{}", description);
+        let unlocked = tokens(1_000_000) * U256::from(unlocked_fraction) / U256::from(RATIO_PRECISION);
+        assert_eq!(calc.locked_amount, tokens(1_000_000) - unlocked, "{}", description);
+    }
+}
+
+#[test]
+fn block_time_units_count_days_in_blocks() {
+    // 172,800 blocks per day at 500ms; 45 days into a 90 day linear schedule
+    let blocks = TimeUnit::Blocks { block_time_ms: 500 };
+    let calc = calculate_pool_vesting(tokens(1_000), U256::zero(), U256::zero(), U256::from(90), U256::from(RATIO_PRECISION), U256::from(1_000 + 45 * 172_800), U256::from(1_000), blocks, ReleaseGranularity::Daily, "linear");
+    assert_eq!(calc.days_passed, U256::from(45));
+    assert_eq!(calc.locked_amount, tokens(500));
+}
+
+fn elapsed(seconds: u64, granularity: ReleaseGranularity) -> Elapsed {
+    Elapsed::between(U256::from(TGE), U256::from(TGE + seconds), TimeUnit::Seconds, granularity)
+}
+
+#[test]
+fn cliff_only_unlocks_everything_at_cliff_end() {
+    let schedule = CliffOnly { tge_percentage: U256::from(10), cliff: U256::from(365 * DAY) };
+    let precision = U256::from(RATIO_PRECISION);
+    assert_eq!(schedule.unlocked_fraction(elapsed(364 * DAY, ReleaseGranularity::Continuous), precision), U256::from(100_000));
+    assert_eq!(schedule.locked(tokens(1_000), elapsed(365 * DAY, ReleaseGranularity::Continuous), precision), U256::zero());
+    assert_eq!(calculate_pool_vesting(tokens(1_000), U256::from(10), U256::from(365), U256::zero(), precision, U256::from(TGE), U256::from(TGE), TimeUnit::Seconds, ReleaseGranularity::Continuous, "cliff").locked_amount, tokens(900));
+}
+
+#[test]
+fn piecewise_interpolates_between_points() {
+    let point = |day: u64, cumulative: u64| (U256::from(day * DAY), U256::from(cumulative));
+    // 10% at day 0, nothing more until day 30, then 90% more by day 130 with a 25% jump at day 80
+    let schedule = Piecewise { points: vec![point(0, 100_000), point(30, 100_000), point(80, 350_000), point(80, 600_000), point(130, 1_000_000)] };
+    let precision = U256::from(RATIO_PRECISION);
+    let cases: &[(u64, ReleaseGranularity, u64)] = &[
+        (0, ReleaseGranularity::Continuous, 100_000),
+        (30 * DAY, ReleaseGranularity::Continuous, 100_000),
+        (55 * DAY, ReleaseGranularity::Continuous, 225_000),
+        (55 * DAY + DAY / 2, ReleaseGranularity::Continuous, 227_500),
+        (55 * DAY + DAY / 2, ReleaseGranularity::Daily, 225_000),
+        (80 * DAY, ReleaseGranularity::Continuous, 600_000),
+        (105 * DAY, ReleaseGranularity::Continuous, 800_000),
+        (130 * DAY, ReleaseGranularity::Continuous, 1_000_000),
+        (10_000 * DAY, ReleaseGranularity::Continuous, 1_000_000),
+    ];
+    for &(seconds, granularity, expected) in cases {
+        assert_eq!(schedule.unlocked_fraction(elapsed(seconds, granularity), precision), U256::from(expected), "{} seconds", seconds);
+    }
+
+    let delayed = Piecewise { points: vec![point(30, 500_000), point(60, 1_000_000)] };
+    assert_eq!(delayed.unlocked_fraction(elapsed(29 * DAY, ReleaseGranularity::Continuous), precision), U256::zero());
+}
+
+fn custom_schedule(tranches: &str) -> Result<Vec<PoolSchedule>, String> {
+    custom_schedule_with_granularity(tranches, ReleaseGranularity::Continuous)
+}
+
+fn custom_schedule_with_granularity(tranches: &str, granularity: ReleaseGranularity) -> Result<Vec<PoolSchedule>, String> {
+    let content = format!(
+        r#"[{{
+            "addresses": [{{ "address": "0x0000000000000000000000000000000000000001", "chain": "Matchain" }}],
+            "balance_at_tge": 1000000,
+            "vesting_type": "custom",
+            "release_granularity": {},
+            "tranches": {}
+        }}]"#,
+        serde_json::to_string(&granularity).unwrap(),
+        tranches
+    );
+    let schedules = config::parse_pool_data(&content)?;
+    config::validate_pool_data(&schedules, U256::from(TGE))?;
+    Ok(schedules)
+}
+
+#[test]
+fn custom_schedules_unlock_by_tranche() {
+    // TGE is 2025-06-15T15:06:40Z, so midnight on 2025-06-16 is 32,000 seconds in and midnight
+    // on 2025-12-12 is 179 days and 32,000 seconds in
+    let schedules = custom_schedule(
+        r#"[
+            { "day": 0, "percentage": 10 },
+            { "date": "2025-06-16", "percentage": 12.5 },
+            { "day": 90, "percentage": 40 },
+            { "date": "2025-12-12", "percentage": 100 }
+        ]"#,
+    )
+    .unwrap();
+    let schedule = &schedules[0];
+
+    let cases: &[(u64, u64)] = &[
+        (0, 100_000),
+        (31_999, 100_000),
+        (32_000, 125_000),
+        (89 * DAY, 125_000),
+        (90 * DAY, 400_000),
+        (179 * DAY + 31_999, 400_000),
+        (179 * DAY + 32_000, 1_000_000),
+        (10_000 * DAY, 1_000_000),
+    ];
+    for &(seconds, unlocked_fraction) in cases {
+        assert_eq!(vest_schedule(schedule, seconds).unlocked_fraction, U256::from(unlocked_fraction), "{} seconds", seconds);
+    }
+    let calc = vest_schedule(schedule, 30 * DAY);
+    assert_eq!(calc.locked_amount, tokens(875_000));
+    assert_eq!((calc.cliff_days, calc.vesting_days), (U256::zero(), U256::from(179)));
+}
+
+#[test]
+fn custom_schedules_are_validated() {
+    let invalid = |tranches: &str| custom_schedule(tranches).unwrap_err();
+
+    assert!(invalid("[]").contains("at least one tranche"));
+    assert!(invalid(r#"[{ "day": 30, "percentage": 50 }, { "day": 30, "percentage": 100 }]"#).contains("does not come after"));
+    assert!(invalid(r#"[{ "day": 60, "percentage": 50 }, { "date": "2025-07-01", "percentage": 100 }]"#).contains("does not come after"));
+    assert!(invalid(r#"[{ "day": 30, "percentage": 50 }, { "day": 60, "percentage": 40 }, { "day": 90, "percentage": 100 }]"#).contains("unlocks less"));
+    assert!(invalid(r#"[{ "day": 30, "percentage": 50 }, { "day": 60, "percentage": 99.9 }]"#).contains("100%"));
+    assert!(invalid(r#"[{ "day": 30, "percentage": 150 }]"#).contains("outside 0-100"));
+    assert!(invalid(r#"[{ "date": "2025-02-30", "percentage": 100 }]"#).contains("Invalid tranche date"));
+    assert!(invalid(r#"[{ "day": 1, "date": "2025-07-01", "percentage": 100 }]"#).contains("exactly one"));
+    // TGE is not at midnight, so whole days would unlock the dated tranche up to a day late
+    let daily = custom_schedule_with_granularity(r#"[{ "date": "2025-06-16", "percentage": 100 }]"#, ReleaseGranularity::Daily).unwrap_err();
+    assert!(daily.contains("'daily' release granularity does not apply"), "{}", daily);
+}
+
+#[test]
+fn schedules_count_from_their_own_start() {
+    let start = TGE + 100 * DAY;
+    let schedule = PoolSchedule {
+        addresses: Vec::new(),
+        tge_percentage: U256::from(10),
+        cliff: U256::from(30),
+        vesting: U256::from(100),
+        vesting_type: "linear".to_string(),
+        balance_at_tge: tokens(1_000),
+        release_granularity: ReleaseGranularity::Daily,
+        tranches: Vec::new(),
+        start_timestamp: Some(U256::from(start)),
+        label: Default::default(),
+    };
+    let at = |timestamp: u64| calculate_schedule_vesting(&schedule, U256::from(RATIO_PRECISION), U256::from(timestamp), U256::from(TGE));
+
+    assert_eq!(at(start - 1).locked_amount, tokens(1_000));
+    assert_eq!(at(start).locked_amount, tokens(900));
+    assert_eq!(at(start + 80 * DAY).locked_amount, tokens(400));
+    assert_eq!(at(start + 80 * DAY).days_until_vesting_ends, U256::from(50));
+
+    // Tranche dates and day offsets are resolved against the start too
+    let custom = PoolSchedule {
+        vesting_type: "custom".to_string(),
+        tranches: vec![
+            TranchePoint { at: TrancheTime::Day(10), cumulative: U256::from(500_000) },
+            TranchePoint { at: TrancheTime::Date(start + 20 * DAY), cumulative: U256::from(1_000_000) },
+        ],
+        ..schedule.clone()
+    };
+    assert_eq!(custom.tranche_offsets(U256::from(TGE)), [(U256::from(10 * DAY), U256::from(500_000)), (U256::from(20 * DAY), U256::from(1_000_000))]);
+}
+
+#[test]
+fn unsorted_tranches_do_not_panic() {
+    let schedule = PoolSchedule {
+        addresses: Vec::new(),
+        tge_percentage: U256::zero(),
+        cliff: U256::zero(),
+        vesting: U256::zero(),
+        vesting_type: "custom".to_string(),
+        balance_at_tge: tokens(1_000),
+        release_granularity: ReleaseGranularity::Continuous,
+        tranches: vec![
+            TranchePoint { at: TrancheTime::Day(20), cumulative: U256::from(500_000) },
+            TranchePoint { at: TrancheTime::Day(10), cumulative: U256::from(1_000_000) },
+        ],
+        start_timestamp: None,
+        label: Default::default(),
+    };
+
+    let calc = vest_schedule(&schedule, 5 * DAY);
+    assert_eq!((calc.cliff_days, calc.vesting_days, calc.locked_amount), (U256::from(20), U256::zero(), tokens(1_000)));
+    assert_eq!(vest_schedule(&schedule, 15 * DAY).locked_amount, U256::zero());
+}
+
+fn vesting_type() -> impl Strategy<Value = &'static str> {
+    prop_oneof![Just("linear"), Just("stepped"), Just("cliff")]
+}
+
+fn granularity() -> impl Strategy<Value = ReleaseGranularity> {
+    prop_oneof![Just(ReleaseGranularity::Continuous), Just(ReleaseGranularity::Daily)]
+}
+
+proptest! {
+    #[test]
+    fn unlocking_is_monotonic_and_bounded(
+        initial in 0u64..10_000_000_000,
+        tge in 0u64..=100,
+        cliff in 0u64..720,
+        vesting in 0u64..2_000,
+        earlier in 0u64..3_000 * DAY,
+        later_offset in 0u64..3_000 * DAY,
+        granularity in granularity(),
+        vesting_type in vesting_type(),
+    ) {
+        let initial = tokens(initial);
+        let before = vest(initial, tge, cliff, vesting, earlier, granularity, vesting_type);
+        let after = vest(initial, tge, cliff, vesting, earlier + later_offset, granularity, vesting_type);
+
+        prop_assert!(before.locked_amount <= initial);
+        prop_assert!(after.locked_amount <= before.locked_amount);
+        prop_assert!(before.unlocked_fraction <= U256::from(RATIO_PRECISION));
+    }
+
+    #[test]
+    fn linear_schedules_fully_unlock_at_vesting_end(
+        initial in 0u64..10_000_000_000,
+        tge in 0u64..=100,
+        cliff in 0u64..720,
+        vesting in 1u64..2_000,
+        granularity in granularity(),
+    ) {
+        let calc = vest(tokens(initial), tge, cliff, vesting, (cliff + vesting) * DAY, granularity, "linear");
+        prop_assert_eq!(calc.locked_amount, U256::zero());
+    }
+}
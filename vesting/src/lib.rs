@@ -0,0 +1,242 @@
+// vesting/src/lib.rs
+//! Vesting math with no I/O: given a schedule and the time elapsed since it started, how much of
+//! an allocation is unlocked. Fractions are scaled by a caller-supplied `precision` (the
+//! StakingPool contracts use `RATIO_PRECISION`).
+//!
+//! The `openapi` feature derives `utoipa::ToSchema` for the types that appear in API responses.
+use primitive_types::U256;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::fmt;
+use std::str::FromStr;
+
+/// Unit in which a schedule's elapsed time is measured before being converted to days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "unit", rename_all = "snake_case")]
+pub enum TimeUnit {
+    /// Wall-clock seconds from block timestamps.
+    Seconds,
+    /// Block numbers, with days derived from a configured block time.
+    Blocks { block_time_ms: u64 },
+    /// Block numbers, with days derived from a contract's own `BLOCKS_PER_DAY`.
+    OnchainBlocks { blocks_per_day: u64 },
+}
+
+impl TimeUnit {
+    /// Units in a day; zero for a block time longer than a day.
+    pub fn units_per_day(&self) -> U256 {
+        match self {
+            TimeUnit::Seconds => U256::from(86_400u64),
+            TimeUnit::Blocks { block_time_ms } => U256::from(86_400_000u64.checked_div(*block_time_ms).unwrap_or_default()),
+            TimeUnit::OnchainBlocks { blocks_per_day } => U256::from(*blocks_per_day),
+        }
+    }
+}
+
+/// How often a linear schedule releases tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseGranularity {
+    /// Release with every second (or block) that passes.
+    #[default]
+    Continuous,
+    /// Release once per whole day, for contracts that unlock daily.
+    Daily,
+}
+
+/// A release granularity that is neither `continuous` nor `daily`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGranularity(pub String);
+
+impl fmt::Display for UnknownGranularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown release granularity '{}', expected continuous or daily", self.0)
+    }
+}
+
+impl std::error::Error for UnknownGranularity {}
+
+impl FromStr for ReleaseGranularity {
+    type Err = UnknownGranularity;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "continuous" => Ok(Self::Continuous),
+            "daily" => Ok(Self::Daily),
+            other => Err(UnknownGranularity(other.to_string())),
+        }
+    }
+}
+
+/// Time elapsed since a schedule started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed {
+    pub units: U256,
+    pub units_per_day: U256,
+    pub granularity: ReleaseGranularity,
+}
+
+impl Elapsed {
+    /// Time from `start` to `current`, both measured in `time_unit`; zero before the start.
+    pub fn between(start: U256, current: U256, time_unit: TimeUnit, granularity: ReleaseGranularity) -> Self {
+        Self { units: current.saturating_sub(start), units_per_day: time_unit.units_per_day(), granularity }
+    }
+
+    pub fn days(&self) -> U256 {
+        self.units.checked_div(self.units_per_day).unwrap_or_default()
+    }
+
+    /// Units that count toward vesting: every unit when continuous, whole days only when daily.
+    fn vesting_units(&self) -> U256 {
+        match self.granularity {
+            ReleaseGranularity::Continuous => self.units,
+            ReleaseGranularity::Daily => self.days() * self.units_per_day,
+        }
+    }
+}
+
+pub trait Schedule {
+    /// Unlocked share of the allocation after `elapsed`, scaled by `precision`.
+    fn unlocked_fraction(&self, elapsed: Elapsed, precision: U256) -> U256;
+
+    fn unlocked(&self, total: U256, elapsed: Elapsed, precision: U256) -> U256 {
+        total.saturating_mul(self.unlocked_fraction(elapsed, precision)).checked_div(precision).unwrap_or_default()
+    }
+
+    fn locked(&self, total: U256, elapsed: Elapsed, precision: U256) -> U256 {
+        total.saturating_sub(self.unlocked(total, elapsed, precision))
+    }
+}
+
+fn percentage(percent: U256, precision: U256) -> U256 {
+    percent * (precision / U256::from(100u64))
+}
+
+/// `tge_percentage` at the start, then linear release over `vesting` once the `cliff` ends. Both
+/// periods are in the schedule's time unit, so block-counted periods need not be whole days.
+/// The TGE share is added on top of vesting progress and the total is capped at fully unlocked.
+/// With no vesting period nothing beyond the TGE share is released.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub tge_percentage: U256,
+    pub cliff: U256,
+    pub vesting: U256,
+}
+
+impl Schedule for Linear {
+    fn unlocked_fraction(&self, elapsed: Elapsed, precision: U256) -> U256 {
+        let tge = percentage(self.tge_percentage, precision);
+        if elapsed.units < self.cliff || self.vesting.is_zero() {
+            return tge;
+        }
+        let progress = elapsed.vesting_units().saturating_sub(self.cliff);
+        let vested = progress.checked_mul(precision).unwrap_or_default() / self.vesting;
+        cmp::min(vested.checked_add(tge).unwrap_or(tge), precision)
+    }
+}
+
+/// `tge_percentage` at the start, then after the `cliff` `steps` releases every `step`, each
+/// unlocking `step_rate` (parts per million) of what is still locked. Periods are in the
+/// schedule's time unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stepped {
+    pub tge_percentage: U256,
+    pub cliff: U256,
+    pub step: U256,
+    pub steps: U256,
+    pub step_rate: U256,
+}
+
+impl Stepped {
+    /// The quarterly schedule used by our stepped allocations: six 90-day steps of 16.67%.
+    pub fn quarterly(tge_percentage: U256, cliff: U256, units_per_day: U256) -> Self {
+        Self {
+            tge_percentage,
+            cliff,
+            step: U256::from(90u64).saturating_mul(units_per_day),
+            steps: U256::from(6u64),
+            step_rate: U256::from(166_700u64),
+        }
+    }
+}
+
+impl Schedule for Stepped {
+    fn unlocked_fraction(&self, elapsed: Elapsed, precision: U256) -> U256 {
+        let tge = percentage(self.tge_percentage, precision);
+        if elapsed.units < self.cliff {
+            return tge;
+        }
+        let periods = cmp::min((elapsed.units - self.cliff).checked_div(self.step).unwrap_or_default(), self.steps);
+        let mut remaining = precision.saturating_sub(tge);
+        let mut unlocked = tge;
+        for _ in 0..periods.as_u64() {
+            let release = remaining * self.step_rate / U256::from(1_000_000u64);
+            unlocked = unlocked.checked_add(release).unwrap_or(unlocked);
+            remaining = remaining.saturating_sub(release);
+        }
+        cmp::min(unlocked, precision)
+    }
+}
+
+/// `tge_percentage` at the start and everything else at once when the `cliff` ends, counted in
+/// the schedule's time unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliffOnly {
+    pub tge_percentage: U256,
+    pub cliff: U256,
+}
+
+impl Schedule for CliffOnly {
+    fn unlocked_fraction(&self, elapsed: Elapsed, precision: U256) -> U256 {
+        if elapsed.units < self.cliff { cmp::min(percentage(self.tge_percentage, precision), precision) } else { precision }
+    }
+}
+
+// Cumulative shares in a piecewise schedule are parts per million
+pub const PIECEWISE_PRECISION: u64 = 1_000_000;
+
+/// Custom schedule through `(offset, cumulative)` points, where `offset` is the time since the
+/// start in the schedule's time unit and `cumulative` is the unlocked share in parts per million.
+/// Unlocking is interpolated linearly between points, so two points at the same offset describe a
+/// one-off release. Nothing is unlocked before the first point and the last point's share holds
+/// afterwards. Points must be sorted by offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piecewise {
+    pub points: Vec<(U256, U256)>,
+}
+
+impl Piecewise {
+    /// Tranches that each unlock at once at their offset: `(offset, cumulative)` points sorted by
+    /// offset.
+    pub fn tranches(tranches: &[(U256, U256)]) -> Self {
+        let mut points = Vec::with_capacity(tranches.len() * 2);
+        let mut previous = U256::zero();
+        for &(offset, cumulative) in tranches {
+            points.push((offset, previous));
+            points.push((offset, cumulative));
+            previous = cumulative;
+        }
+        Self { points }
+    }
+}
+
+impl Schedule for Piecewise {
+    fn unlocked_fraction(&self, elapsed: Elapsed, precision: U256) -> U256 {
+        let units = elapsed.vesting_units();
+        // The last point already reached, and the one after it
+        let reached = self.points.iter().rposition(|&(offset, _)| offset <= units);
+        let cumulative = match reached {
+            None => U256::zero(),
+            Some(i) => match self.points.get(i + 1) {
+                Some(&(next_offset, next)) if next > self.points[i].1 => {
+                    let (offset, current) = self.points[i];
+                    current + (next - current).saturating_mul(units - offset) / (next_offset - offset)
+                }
+                _ => self.points[i].1,
+            },
+        };
+        cmp::min(cumulative.saturating_mul(precision) / U256::from(PIECEWISE_PRECISION), precision)
+    }
+}